@@ -1,14 +1,25 @@
 use napi::Error as NapiError;
 use napi_derive::napi;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::error::Error;
 use std::fmt;
+use std::sync::Arc;
 
 #[napi]
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub enum ErrorKind {
     NotFound,
     BadRequest,
+    Forbidden,
     ValidationError,
     InternalError,
+    MethodNotAllowed,
+    NotAcceptable,
+    UriTooLong,
+    TooManyRequests,
+    ServiceUnavailable,
+    GatewayTimeout,
 }
 
 impl fmt::Display for ErrorKind {
@@ -16,18 +27,91 @@ impl fmt::Display for ErrorKind {
         match self {
             ErrorKind::NotFound => write!(f, "Not Found"),
             ErrorKind::BadRequest => write!(f, "Bad Request"),
+            ErrorKind::Forbidden => write!(f, "Forbidden"),
             ErrorKind::ValidationError => write!(f, "Validation Error"),
             ErrorKind::InternalError => write!(f, "Internal Error"),
+            ErrorKind::MethodNotAllowed => write!(f, "Method Not Allowed"),
+            ErrorKind::NotAcceptable => write!(f, "Not Acceptable"),
+            ErrorKind::UriTooLong => write!(f, "URI Too Long"),
+            ErrorKind::TooManyRequests => write!(f, "Too Many Requests"),
+            ErrorKind::ServiceUnavailable => write!(f, "Service Unavailable"),
+            ErrorKind::GatewayTimeout => write!(f, "Gateway Timeout"),
         }
     }
 }
 
+impl ErrorKind {
+    pub fn status(&self) -> u16 {
+        match self {
+            ErrorKind::NotFound => 404,
+            ErrorKind::BadRequest => 400,
+            ErrorKind::Forbidden => 403,
+            ErrorKind::ValidationError => 422,
+            ErrorKind::InternalError => 500,
+            ErrorKind::MethodNotAllowed => 405,
+            ErrorKind::NotAcceptable => 406,
+            ErrorKind::UriTooLong => 414,
+            ErrorKind::TooManyRequests => 429,
+            ErrorKind::ServiceUnavailable => 503,
+            ErrorKind::GatewayTimeout => 504,
+        }
+    }
+
+    /// Stable machine-readable code, safe for clients to match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::BadRequest => "bad_request",
+            ErrorKind::Forbidden => "forbidden",
+            ErrorKind::ValidationError => "validation_error",
+            ErrorKind::InternalError => "internal_error",
+            ErrorKind::MethodNotAllowed => "method_not_allowed",
+            ErrorKind::NotAcceptable => "not_acceptable",
+            ErrorKind::UriTooLong => "uri_too_long",
+            ErrorKind::TooManyRequests => "too_many_requests",
+            ErrorKind::ServiceUnavailable => "service_unavailable",
+            ErrorKind::GatewayTimeout => "gateway_timeout",
+        }
+    }
+
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.status())
+    }
+
+    /// Whether repeating the same request could succeed: timeouts,
+    /// throttling and upstream/availability failures.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.status(), 408 | 429 | 502 | 503 | 504)
+    }
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+/// Structured form of a [`ZapError`], shared by error hooks and the default
+/// renderer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorDetail {
+    pub code: &'static str,
+    pub message: String,
+    pub status: u16,
+    pub details: Option<String>,
+}
+
 #[napi]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ZapError {
     pub kind: ErrorKind,
     pub message: String,
     pub details: Option<String>,
+    pub errors: Vec<FieldError>,
+    /// Underlying cause, shared between clones.
+    source: Option<Arc<dyn Error + Send + Sync>>,
 }
 
 impl fmt::Display for ZapError {
@@ -42,6 +126,8 @@ impl ZapError {
             kind: ErrorKind::NotFound,
             message: message.into(),
             details: None,
+            errors: Vec::new(),
+            source: None,
         }
     }
 
@@ -50,6 +136,29 @@ impl ZapError {
             kind: ErrorKind::BadRequest,
             message: message.into(),
             details: None,
+            errors: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self {
+            kind: ErrorKind::Forbidden,
+            message: message.into(),
+            details: None,
+            errors: Vec::new(),
+            source: None,
+        }
+    }
+
+    /// `details` carries the allowed methods in `Allow` header format.
+    pub fn method_not_allowed(message: impl Into<String>, allowed: &[String]) -> Self {
+        Self {
+            kind: ErrorKind::MethodNotAllowed,
+            message: message.into(),
+            details: Some(allowed.join(", ")),
+            errors: Vec::new(),
+            source: None,
         }
     }
 
@@ -58,6 +167,68 @@ impl ZapError {
             kind: ErrorKind::ValidationError,
             message: message.into(),
             details,
+            errors: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn validation(message: impl Into<String>, errors: Vec<FieldError>) -> Self {
+        Self {
+            kind: ErrorKind::ValidationError,
+            message: message.into(),
+            details: None,
+            errors,
+            source: None,
+        }
+    }
+
+    pub fn uri_too_long(message: impl Into<String>) -> Self {
+        Self {
+            kind: ErrorKind::UriTooLong,
+            message: message.into(),
+            details: None,
+            errors: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn not_acceptable(message: impl Into<String>) -> Self {
+        Self {
+            kind: ErrorKind::NotAcceptable,
+            message: message.into(),
+            details: None,
+            errors: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn too_many_requests(message: impl Into<String>) -> Self {
+        Self {
+            kind: ErrorKind::TooManyRequests,
+            message: message.into(),
+            details: None,
+            errors: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn service_unavailable(message: impl Into<String>) -> Self {
+        Self {
+            kind: ErrorKind::ServiceUnavailable,
+            message: message.into(),
+            details: None,
+            errors: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn gateway_timeout(message: impl Into<String>) -> Self {
+        Self {
+            kind: ErrorKind::GatewayTimeout,
+            message: message.into(),
+            details: None,
+            errors: Vec::new(),
+            source: None,
         }
     }
 
@@ -66,16 +237,73 @@ impl ZapError {
             kind: ErrorKind::InternalError,
             message: message.into(),
             details: None,
+            errors: Vec::new(),
+            source: None,
+        }
+    }
+
+    /// An internal error caused by `source`, which `Error::source` returns.
+    pub fn internal_with_source(
+        message: impl Into<String>,
+        source: impl Error + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            source: Some(Arc::new(source)),
+            ..Self::internal(message)
         }
     }
 }
 
+impl ZapError {
+    pub fn status(&self) -> u16 {
+        self.kind.status()
+    }
+
+    pub fn is_client_error(&self) -> bool {
+        self.kind.is_client_error()
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind.is_retryable()
+    }
+
+    pub fn to_detail(&self) -> ErrorDetail {
+        ErrorDetail {
+            code: self.kind.code(),
+            message: self.message.clone(),
+            status: self.status(),
+            details: self.details.clone(),
+        }
+    }
+
+    /// Renders the error as the JSON body sent to clients. Validation errors
+    /// carrying field errors use the `{"errors": [...]}` shape.
+    pub fn to_json(&self) -> Value {
+        if !self.errors.is_empty() {
+            return json!({ "errors": self.errors });
+        }
+        json!({ "error": self.to_detail() })
+    }
+}
+
+impl Error for ZapError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|source| source as &(dyn Error + 'static))
+    }
+}
+
 impl From<NapiError> for ZapError {
     fn from(error: NapiError) -> Self {
         Self::internal(error.to_string())
     }
 }
 
+impl From<serde_json::Error> for ZapError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::bad_request(error.to_string())
+    }
+}
+
 impl From<ZapError> for NapiError {
     fn from(error: ZapError) -> Self {
         NapiError::from_reason(error.to_string())
@@ -0,0 +1,79 @@
+//! Typed accessors for request headers and content negotiation.
+
+use std::fmt::Display;
+use std::str::FromStr;
+use mime::Mime;
+use crate::error::ZapError;
+use crate::types::JsRequest;
+
+/// Header `name` parsed as `T`, or `None` when the request doesn't send it.
+/// A value that doesn't parse is a 400 naming the header.
+pub fn header<T>(request: &JsRequest, name: &str) -> Result<Option<T>, ZapError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    request
+        .header(name)
+        .map(|value| {
+            value.trim().parse().map_err(|e| {
+                ZapError::bad_request(format!("invalid '{}' header '{}': {}", name, value, e))
+            })
+        })
+        .transpose()
+}
+
+/// Like [`header`], but a missing header is a 400 as well.
+pub fn require_header<T>(request: &JsRequest, name: &str) -> Result<T, ZapError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    header(request, name)?
+        .ok_or_else(|| ZapError::bad_request(format!("missing '{}' header", name)))
+}
+
+/// The request's `Content-Type`.
+pub fn content_type(request: &JsRequest) -> Result<Option<Mime>, ZapError> {
+    header(request, "content-type")
+}
+
+/// The request's `Content-Length`.
+pub fn content_length(request: &JsRequest) -> Result<Option<usize>, ZapError> {
+    header(request, "content-length")
+}
+
+/// Best match in `available` for the request's `Accept-Language`, or
+/// `default`. Ranges are tried by quality; a range matches an available
+/// locale exactly or by language subtag, so `en-US` picks `en`.
+pub fn negotiate_locale(request: &JsRequest, available: &[&str], default: &str) -> String {
+    let header = request.headers_all("accept-language").join(",");
+    let mut ranges: Vec<(&str, f32)> = header.split(',').filter_map(language_range).collect();
+    // Stable, so equal qualities keep the client's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranges
+        .iter()
+        .find_map(|(tag, _)| available_match(tag, available))
+        .unwrap_or(default)
+        .to_string()
+}
+
+/// A language range and its quality; `None` for `*`, malformed and q=0 ranges.
+fn language_range(range: &str) -> Option<(&str, f32)> {
+    let mut parts = range.split(';').map(str::trim);
+    let tag = parts.next().filter(|tag| !tag.is_empty() && *tag != "*")?;
+    let quality = match parts.find_map(|param| param.strip_prefix("q=")) {
+        Some(q) => q.parse::<f32>().ok()?,
+        None => 1.0,
+    };
+    (quality > 0.0).then_some((tag, quality))
+}
+
+fn available_match<'a>(tag: &str, available: &[&'a str]) -> Option<&'a str> {
+    let language = |locale: &str| locale.split('-').next().unwrap_or_default().to_ascii_lowercase();
+    available
+        .iter()
+        .find(|locale| locale.eq_ignore_ascii_case(tag))
+        .or_else(|| available.iter().find(|locale| language(locale) == language(tag)))
+        .copied()
+}
@@ -1,19 +1,25 @@
+use std::sync::Arc;
 use crate::error::ZapError;
 use crate::types::{JsRequest, JsResponse};
-use crate::middleware::MiddlewareChain;
 
+type HandlerFn = dyn Fn(JsRequest) -> Result<JsResponse, ZapError> + Send + Sync;
+
+#[derive(Clone)]
 pub struct Handle {
-    middleware: MiddlewareChain,
+    handler: Arc<HandlerFn>,
 }
 
 impl Handle {
-    pub fn new() -> Self {
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: Fn(JsRequest) -> Result<JsResponse, ZapError> + Send + Sync + 'static,
+    {
         Self {
-            middleware: MiddlewareChain::new(),
+            handler: Arc::new(handler),
         }
     }
 
     pub fn handle(&self, request: JsRequest) -> Result<JsResponse, ZapError> {
-        self.middleware.execute(request)
+        (self.handler)(request)
     }
-} 
\ No newline at end of file
+}
@@ -4,9 +4,21 @@ use napi::{Result, JsObject, Env};
 pub mod router;
 pub mod hooks;
 pub mod middleware;
+pub mod error;
+pub mod types;
+pub mod handle;
+pub mod transport;
+pub mod extract;
 
-pub use router::{Router, RouteConfig, RouteParams};
+#[cfg(test)]
+mod tests;
+
+pub use router::{Conflict, Deprecation, LookupResult, MatchExplanation, MatchStep, Router, RouteConfig, RouteDefinition, RouteParams, RouteScope, RouteSummary, HostScope, RouteGroup, Scope};
 pub use middleware::{MiddlewareChain, Guard};
+pub use error::{ErrorDetail, ErrorKind, FieldError, ZapError};
+pub use hooks::Hooks;
+pub use types::{JsRequest, JsResponse};
+pub use transport::JsRouter;
 
 #[napi]
 pub fn get_version() -> String {
@@ -0,0 +1,68 @@
+//! Native middleware limiting in-flight requests per client key.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use crate::error::ZapError;
+use crate::transport::Middleware;
+use crate::types::JsRequest;
+
+type Semaphores = Arc<Mutex<HashMap<String, Arc<Semaphore>>>>;
+
+/// Allows at most `limit` requests per key through the rest of the chain at
+/// once; the excess is rejected with 429. `key` picks the client key, such
+/// as an API key header. Requests without a key are not limited.
+///
+/// The permit is held until the downstream chain finishes, and is released
+/// whether it returns a response, an error, or panics.
+pub fn limit_per_key<K>(limit: usize, key: K) -> Middleware
+where
+    K: Fn(&JsRequest) -> Option<String> + Send + Sync + 'static,
+{
+    let semaphores: Semaphores = Arc::new(Mutex::new(HashMap::new()));
+    Box::new(move |request, next| {
+        let Some(key) = key(&request) else {
+            return next(request);
+        };
+        let semaphore = semaphores.lock().unwrap()
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+            .clone();
+        let Ok(permit) = Arc::clone(&semaphore).try_acquire_owned() else {
+            return Box::pin(async move {
+                Err(ZapError::too_many_requests(format!("too many concurrent requests for '{}'", key)))
+            });
+        };
+
+        let held = HeldPermit {
+            permit: Some(permit),
+            semaphore,
+            semaphores: Arc::clone(&semaphores),
+            key,
+        };
+        Box::pin(async move {
+            let _held = held;
+            next(request).await
+        })
+    })
+}
+
+/// Releases a permit on drop and forgets the key's semaphore once no
+/// request holds it.
+struct HeldPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    semaphore: Arc<Semaphore>,
+    semaphores: Semaphores,
+    key: String,
+}
+
+impl Drop for HeldPermit {
+    fn drop(&mut self) {
+        self.permit.take();
+        let mut semaphores = self.semaphores.lock().unwrap();
+        // One reference in the map, one here: nobody else is in flight
+        if Arc::strong_count(&self.semaphore) == 2 {
+            semaphores.remove(&self.key);
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! Native middleware negotiating the request locale.
+
+use crate::extract::negotiate_locale;
+use crate::transport::Middleware;
+
+/// Picks the best of `available` for each request's `Accept-Language`,
+/// falling back to `default`, and stores it for `JsRequest::locale`.
+pub fn negotiate(available: Vec<String>, default: impl Into<String>) -> Middleware {
+    let default = default.into();
+    Box::new(move |mut request, next| {
+        let available: Vec<&str> = available.iter().map(String::as_str).collect();
+        request.locale = Some(negotiate_locale(&request, &available, &default));
+        next(request)
+    })
+}
@@ -4,6 +4,9 @@ use std::collections::HashMap;
 use std::sync::Mutex;
 use std::sync::atomic::{AtomicU32, Ordering};
 
+pub mod concurrency;
+pub mod locale;
+
 type MiddlewareId = u32;
 type MiddlewareFn = Arc<Mutex<Option<JsObject>>>;
 
@@ -0,0 +1,104 @@
+use napi_derive::napi;
+
+/// A route as it was registered, kept for diagnostics.
+#[derive(Debug, Clone)]
+pub(crate) struct RegisteredRoute {
+    pub id: u32,
+    pub method: String,
+    pub path: String,
+    pub host: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub method: String,
+    pub route: String,
+    pub conflicts_with: String,
+    pub reason: String,
+}
+
+enum Shape<'a> {
+    Static(&'a str),
+    Param(&'a str),
+    Wildcard,
+}
+
+impl Shape<'_> {
+    fn same_edge(&self, other: &Shape) -> bool {
+        match (self, other) {
+            (Shape::Static(a), Shape::Static(b)) => a == b,
+            (Shape::Param(_), Shape::Param(_)) => true,
+            (Shape::Wildcard, Shape::Wildcard) => true,
+            _ => false,
+        }
+    }
+}
+
+fn shape(path: &str, keep_empty: bool) -> Vec<Shape<'_>> {
+    let path = path.strip_prefix('/').unwrap_or(path);
+    if path.is_empty() {
+        return Vec::new();
+    }
+    path.split('/')
+        .filter(|s| keep_empty || !s.is_empty())
+        .map(|segment| {
+            if let Some(name) = segment.strip_prefix(':') {
+                Shape::Param(name)
+            } else if segment == "*" {
+                Shape::Wildcard
+            } else {
+                Shape::Static(segment)
+            }
+        })
+        .collect()
+}
+
+/// Reports routes that can never match as registered. Two patterns that
+/// walk the same trie edges end on the same node, so the later registration
+/// replaces the earlier handler. Patterns sharing a parameter edge under
+/// different names share one node too, so the later route sees the earlier
+/// route's parameter name. With `keep_empty`, empty segments are edges
+/// of their own, as they are in the trie.
+pub(crate) fn find_conflicts(routes: &[RegisteredRoute], keep_empty: bool) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+
+    for (i, earlier) in routes.iter().enumerate() {
+        for later in &routes[i + 1..] {
+            if earlier.method != later.method || earlier.host != later.host {
+                continue;
+            }
+
+            let a = shape(&earlier.path, keep_empty);
+            let b = shape(&later.path, keep_empty);
+            if a.len() == b.len() && a.iter().zip(&b).all(|(x, y)| x.same_edge(y)) {
+                conflicts.push(Conflict {
+                    method: earlier.method.clone(),
+                    route: earlier.path.clone(),
+                    conflicts_with: later.path.clone(),
+                    reason: "handler replaced by a later route with the same shape".to_string(),
+                });
+                continue;
+            }
+
+            for (x, y) in a.iter().zip(&b) {
+                if !x.same_edge(y) {
+                    break;
+                }
+                if let (Shape::Param(first), Shape::Param(second)) = (x, y) {
+                    if first != second {
+                        conflicts.push(Conflict {
+                            method: later.method.clone(),
+                            route: later.path.clone(),
+                            conflicts_with: earlier.path.clone(),
+                            reason: format!("parameter ':{}' is captured as ':{}'", second, first),
+                        });
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    conflicts
+}
@@ -1,18 +1,29 @@
 mod trie;
+mod scope;
+mod conflicts;
+mod query;
 
 use std::sync::Mutex;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::collections::HashMap;
-use napi::{Result, JsObject, Env, NapiRaw, sys};
+use napi::{Error, Result, Status, JsObject, Env, NapiRaw, sys};
 use napi_derive::napi;
 use napi::bindgen_prelude::ToNapiValue;
+use serde_json::Value;
+use crate::error::{ErrorKind, ZapError};
 use crate::hooks::Hooks;
 use crate::middleware::MiddlewareChain;
 
-pub use trie::{RouteParams, TrieNode};
+pub use trie::{MatchExplanation, MatchStep, RouteParams, RouteTable, TrieNode};
+pub use scope::{HostScope, RouteGroup, RouteScope, Scope};
+pub use conflicts::Conflict;
+use conflicts::RegisteredRoute;
 
 type HandlerId = u32;
 
+const DEFAULT_MAX_URI_LENGTH: u32 = 8 * 1024;
+const DEFAULT_MAX_PATH_SEGMENTS: u32 = 64;
+
 #[derive(Debug)]
 pub struct HandlerInfo {
     pub id: HandlerId,
@@ -28,21 +39,131 @@ impl ToNapiValue for HandlerInfo {
     }
 }
 
+/// Outcome of resolving a request, distinguishing an unknown path from a
+/// path that exists under other methods.
+#[derive(Debug)]
+pub enum LookupResult {
+    Found(HandlerInfo),
+    MethodNotAllowed(Vec<String>),
+    NotFound,
+}
+
 #[napi(object)]
+#[derive(Default)]
 pub struct RouteConfig {
     pub middleware: Option<Vec<u32>>,
     pub guards: Option<Vec<u32>>,
     pub validation: Option<JsObject>,
     pub transform: Option<JsObject>,
+    /// Overrides the router-wide handler timeout for this route.
+    pub timeout_ms: Option<u32>,
+    pub deprecation: Option<Deprecation>,
+    /// Arbitrary data for middleware, such as required auth scopes.
+    pub meta: Option<HashMap<String, Value>>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone, Default)]
+pub struct Deprecation {
+    /// HTTP-date after which the route will be removed.
+    pub sunset: Option<String>,
+}
+
+/// A registered route as reported by [`Router::routes`].
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct RouteSummary {
+    pub id: HandlerId,
+    pub method: String,
+    pub path: String,
+    pub host: Option<String>,
+    pub deprecation: Option<Deprecation>,
+}
+
+#[napi(object)]
+pub struct RouteDefinition {
+    pub method: String,
+    pub path: String,
+    pub config: Option<RouteConfig>,
+}
+
+/// Accepts any RFC 7230 token, so extension methods such as `PROPFIND`
+/// route like the standard ones.
+fn validate_method(method: &str) -> std::result::Result<(), String> {
+    let is_tchar = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+    if method.is_empty() || !method.chars().all(is_tchar) {
+        return Err(format!("invalid method '{}'", method));
+    }
+    Ok(())
+}
+
+fn validate_pattern(path: &str) -> std::result::Result<(), String> {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    for (i, segment) in segments.iter().enumerate() {
+        if *segment == ":" {
+            return Err(format!("empty parameter name in '{}'", path));
+        }
+        if *segment == "*" && i != segments.len() - 1 {
+            return Err(format!("wildcard must be the last segment in '{}'", path));
+        }
+    }
+    Ok(())
+}
+
+/// Collapses empty and `.` segments and resolves `..`, failing when the
+/// path would climb above the root.
+fn normalize_path(path: &str) -> std::result::Result<String, ZapError> {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                if segments.pop().is_none() {
+                    return Err(ZapError::bad_request(format!("path escapes root: {}", path)));
+                }
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Ok(format!("/{}", segments.join("/")))
+}
+
+/// Splits an absolute-form request target (`http://host/path`) into its
+/// authority and origin-form remainder. Origin-form targets pass through
+/// with no authority.
+fn split_authority(uri: &str) -> std::result::Result<(Option<&str>, &str), ZapError> {
+    let scheme_len = ["http://", "https://"].iter()
+        .find(|scheme| uri.get(..scheme.len()).is_some_and(|s| s.eq_ignore_ascii_case(scheme)))
+        .map(|scheme| scheme.len());
+    let Some(scheme_len) = scheme_len else {
+        return Ok((None, uri));
+    };
+
+    let rest = &uri[scheme_len..];
+    let end = rest.find(['/', '?']).unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(end);
+    if authority.is_empty() || authority.contains('@') {
+        return Err(ZapError::bad_request(format!("invalid authority in '{}'", uri)));
+    }
+    Ok((Some(authority), path))
 }
 
 #[napi(js_name = "Router")]
 pub struct Router {
-    routes: Mutex<TrieNode>,
+    routes: Mutex<RouteTable>,
     next_id: AtomicU32,
     hooks: Hooks,
     middleware_chain: MiddlewareChain,
     route_configs: Mutex<HashMap<HandlerId, RouteConfig>>,
+    normalize_paths: AtomicBool,
+    host_routes: Mutex<Vec<(String, RouteTable)>>,
+    handler_timeout_ms: Mutex<Option<u32>>,
+    registered: Mutex<Vec<RegisteredRoute>>,
+    catch_all: Mutex<Option<HandlerId>>,
+    error_handlers: Mutex<HashMap<ErrorKind, HandlerId>>,
+    max_uri_length: AtomicU32,
+    max_path_segments: AtomicU32,
+    allow_empty_segments: AtomicBool,
 }
 
 #[napi]
@@ -50,11 +171,20 @@ impl Router {
     #[napi(constructor)]
     pub fn new(hooks: Hooks) -> Self {
         Self {
-            routes: Mutex::new(TrieNode::new()),
+            routes: Mutex::new(RouteTable::new()),
             next_id: AtomicU32::new(1),
             hooks,
             middleware_chain: MiddlewareChain::new(),
             route_configs: Mutex::new(HashMap::new()),
+            normalize_paths: AtomicBool::new(false),
+            host_routes: Mutex::new(Vec::new()),
+            handler_timeout_ms: Mutex::new(None),
+            registered: Mutex::new(Vec::new()),
+            catch_all: Mutex::new(None),
+            error_handlers: Mutex::new(HashMap::new()),
+            max_uri_length: AtomicU32::new(DEFAULT_MAX_URI_LENGTH),
+            max_path_segments: AtomicU32::new(DEFAULT_MAX_PATH_SEGMENTS),
+            allow_empty_segments: AtomicBool::new(false),
         }
     }
 
@@ -65,27 +195,175 @@ impl Router {
 
     #[napi]
     pub fn register(&self, method: String, path: String, config: Option<RouteConfig>) -> Result<HandlerId> {
-        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
-        let mut routes = self.routes.lock().unwrap();
-        let full_path = format!("{}/{}", method, path);
-        routes.insert(&full_path, id);
+        self.register_route(None, &method, &path, config)
+    }
 
-        if let Some(config) = config {
-            let mut configs = self.route_configs.lock().unwrap();
-            configs.insert(id, config);
+    /// Registers a route that only matches requests whose `Host` matches
+    /// `host`. See [`Router::host`] for the pattern syntax.
+    #[napi]
+    pub fn register_for_host(&self, host: String, method: String, path: String, config: Option<RouteConfig>) -> Result<HandlerId> {
+        self.register_route(Some(&host), &method, &path, config)
+    }
+
+    /// Registers a batch of routes. Every pattern is validated before any
+    /// route is inserted, so a failing batch leaves the router unchanged.
+    #[napi]
+    pub fn register_all(&self, routes: Vec<RouteDefinition>) -> Result<Vec<HandlerId>> {
+        for (index, route) in routes.iter().enumerate() {
+            validate_method(&route.method)
+                .and_then(|_| validate_pattern(&route.path))
+                .map_err(|reason| {
+                    Error::new(Status::InvalidArg, format!("route {}: {}", index, reason))
+                })?;
         }
 
-        Ok(id)
+        routes.into_iter()
+            .map(|route| self.register(route.method, route.path, route.config))
+            .collect()
     }
 
+    /// When enabled, lookups collapse duplicate slashes and resolve `.`/`..`
+    /// segments before matching. The normalized path is matched directly;
+    /// no redirect is issued. Paths escaping the root are rejected.
+    #[napi]
+    pub fn set_path_normalization(&self, enabled: bool) {
+        self.normalize_paths.store(enabled, Ordering::SeqCst);
+    }
+
+    /// When enabled, empty segments are significant: `/a//b` no longer
+    /// matches `/a/b`, and a trailing slash adds an empty final segment, so
+    /// `/a/` and `/a` are distinct routes. Params never match an empty
+    /// segment. Path normalization, if also enabled, collapses empty segments
+    /// before matching. Set this before registering routes.
+    #[napi]
+    pub fn set_allow_empty_segments(&self, enabled: bool) {
+        self.allow_empty_segments.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Longest URI, in bytes, accepted by lookups. Defaults to 8 KiB.
+    #[napi]
+    pub fn set_max_uri_length(&self, max: u32) {
+        self.max_uri_length.store(max, Ordering::SeqCst);
+    }
+
+    /// Most path segments accepted by lookups. Defaults to 64.
+    #[napi]
+    pub fn set_max_path_segments(&self, max: u32) {
+        self.max_path_segments.store(max, Ordering::SeqCst);
+    }
+
+    /// Looks up a route. Absolute-form targets (`http://host/path`) are
+    /// routed by their authority as if it were the `Host` header.
     #[napi(js_name = "getHandlerInfo")]
     pub fn get_handler_info(&self, method: String, path: String) -> Result<Option<HandlerInfo>> {
-        Ok(if let Ok(routes) = self.routes.lock() {
-            let full_path = format!("{}/{}", method, path);
-            routes.find(&full_path).map(|(id, params)| HandlerInfo { id, params })
-        } else {
-            None
-        })
+        Ok(self.find_handler(&method, &path)?)
+    }
+
+    /// Registers a handler for any method and path that no other route
+    /// matches. The unmatched path is captured as `*`.
+    #[napi]
+    pub fn catch_all(&self, config: Option<RouteConfig>) -> Result<HandlerId> {
+        let id = self.next_handler(config);
+        *self.catch_all.lock().unwrap() = Some(id);
+        Ok(id)
+    }
+
+    /// Registers a handler for one kind of error, replacing any previous
+    /// handler for that kind.
+    #[napi]
+    pub fn on_error(&self, kind: ErrorKind, config: Option<RouteConfig>) -> Result<HandlerId> {
+        let id = self.next_handler(config);
+        self.error_handlers.lock().unwrap().insert(kind, id);
+        Ok(id)
+    }
+
+    /// Handler registered for `kind`. `None` means the error should go
+    /// through the generic error hooks instead.
+    #[napi]
+    pub fn get_error_handler(&self, kind: ErrorKind) -> Option<HandlerId> {
+        self.error_handlers.lock().unwrap().get(&kind).copied()
+    }
+
+    /// Methods registered for `path`, sorted. Empty when the path is unknown.
+    #[napi]
+    pub fn allowed_methods(&self, path: String) -> Result<Vec<String>> {
+        Ok(self.find_allowed_methods(&path)?)
+    }
+
+    /// Looks up a route for a request's `Host` header. Host scopes are tried
+    /// in registration order; routes registered outside any host scope match
+    /// every host and are used when no scoped route matches. The authority
+    /// of an absolute-form `path` takes precedence over `host`.
+    #[napi(js_name = "getHandlerInfoForHost")]
+    pub fn get_handler_info_for_host(&self, host: String, method: String, path: String) -> Result<Option<HandlerInfo>> {
+        Ok(self.find_handler_for_host(&host, &method, &path)?)
+    }
+
+    /// Every registered route, in registration order.
+    #[napi]
+    pub fn routes(&self) -> Vec<RouteSummary> {
+        let configs = self.route_configs.lock().unwrap();
+        self.registered.lock().unwrap()
+            .iter()
+            .map(|route| RouteSummary {
+                id: route.id,
+                method: route.method.clone(),
+                path: route.path.clone(),
+                host: route.host.clone(),
+                deprecation: configs.get(&route.id).and_then(|config| config.deprecation.clone()),
+            })
+            .collect()
+    }
+
+    /// Lists registered routes that cannot behave as written, such as a
+    /// route whose handler was replaced by a later registration.
+    #[napi]
+    pub fn describe_conflicts(&self) -> Vec<Conflict> {
+        conflicts::find_conflicts(&self.registered.lock().unwrap(), self.keep_empty())
+    }
+
+    /// Number of distinct routes, across methods and host scopes. A route
+    /// replaced by a later registration counts once.
+    #[napi]
+    pub fn route_count(&self) -> u32 {
+        let hosts = self.host_routes.lock().unwrap();
+        let count = self.routes.lock().unwrap().route_count()
+            + hosts.iter().map(|(_, routes)| routes.route_count()).sum::<usize>();
+        count as u32
+    }
+
+    /// Number of trie nodes, across methods and host scopes.
+    #[napi]
+    pub fn trie_node_count(&self) -> u32 {
+        let hosts = self.host_routes.lock().unwrap();
+        let count = self.routes.lock().unwrap().node_count()
+            + hosts.iter().map(|(_, routes)| routes.node_count()).sum::<usize>();
+        count as u32
+    }
+
+    /// Explains how `path` resolves against routes registered outside any
+    /// host scope. The first step is the method; the catch-all route is not
+    /// considered.
+    #[napi]
+    pub fn explain_match(&self, method: String, path: String) -> Result<MatchExplanation> {
+        validate_method(&method).map_err(ZapError::bad_request)?;
+        let (path, _) = self.prepare_path(&path)?;
+        let routes = self.routes.lock().unwrap();
+        Ok(routes.explain(&method, &path, self.keep_empty()))
+    }
+
+    /// Renders the route tries for debugging, one per method; host-scoped
+    /// tries follow under a `host <pattern>` line.
+    #[napi]
+    pub fn dump_trie(&self) -> String {
+        let mut out = self.routes.lock().unwrap().dump();
+        for (pattern, routes) in self.host_routes.lock().unwrap().iter() {
+            out.push_str(&format!("host {}\n", pattern));
+            for line in routes.dump().lines() {
+                out.push_str(&format!("  {}\n", line));
+            }
+        }
+        out
     }
 
     #[napi]
@@ -116,6 +394,59 @@ impl Router {
         None
     }
 
+    #[napi]
+    pub fn set_handler_timeout(&self, timeout_ms: Option<u32>) {
+        *self.handler_timeout_ms.lock().unwrap() = timeout_ms;
+    }
+
+    /// Timeout to apply when running a handler: the route's own
+    /// `timeoutMs` if set, otherwise the router-wide default.
+    #[napi]
+    pub fn get_timeout(&self, handler_id: HandlerId) -> Option<u32> {
+        let configs = self.route_configs.lock().unwrap();
+        configs.get(&handler_id)
+            .and_then(|config| config.timeout_ms)
+            .or(*self.handler_timeout_ms.lock().unwrap())
+    }
+
+    #[napi]
+    pub fn get_middleware_ids(&self, handler_id: HandlerId) -> Vec<u32> {
+        let configs = self.route_configs.lock().unwrap();
+        configs.get(&handler_id)
+            .and_then(|config| config.middleware.clone())
+            .unwrap_or_default()
+    }
+
+    #[napi]
+    pub fn get_guard_ids(&self, handler_id: HandlerId) -> Vec<u32> {
+        let configs = self.route_configs.lock().unwrap();
+        configs.get(&handler_id)
+            .and_then(|config| config.guards.clone())
+            .unwrap_or_default()
+    }
+
+    /// Response headers announcing that the route is deprecated, for the
+    /// JS side to add to every response from it.
+    #[napi]
+    pub fn get_deprecation_headers(&self, handler_id: HandlerId) -> Option<HashMap<String, String>> {
+        let configs = self.route_configs.lock().unwrap();
+        let deprecation = configs.get(&handler_id)?.deprecation.as_ref()?;
+
+        let mut headers = HashMap::new();
+        headers.insert("Deprecation".to_string(), "true".to_string());
+        if let Some(sunset) = &deprecation.sunset {
+            headers.insert("Sunset".to_string(), sunset.clone());
+        }
+        Some(headers)
+    }
+
+    /// Metadata attached to the route through `RouteConfig.meta`.
+    #[napi]
+    pub fn get_route_meta(&self, handler_id: HandlerId) -> Option<HashMap<String, Value>> {
+        let configs = self.route_configs.lock().unwrap();
+        configs.get(&handler_id)?.meta.clone()
+    }
+
     #[napi]
     pub fn get_validation(&self, handler_id: HandlerId) -> Option<JsObject> {
         let mut configs = self.route_configs.lock().unwrap();
@@ -129,4 +460,185 @@ impl Router {
         configs.get_mut(&handler_id)
             .and_then(|config| config.transform.take())
     }
-} 
\ No newline at end of file
+}
+
+impl Router {
+    pub fn at(&self, path: &str) -> RouteScope<'_> {
+        RouteScope::new(self, path)
+    }
+
+    pub fn group(&self, prefix: &str) -> RouteGroup<'_> {
+        RouteGroup::new(self, prefix)
+    }
+
+    pub fn scope(&self) -> Scope<'_> {
+        Scope::new(self)
+    }
+
+    /// Scopes route registration to requests whose `Host` matches `pattern`.
+    /// Labels starting with `:` capture that part of the host as a param,
+    /// e.g. `:tenant.example.com`.
+    pub fn host(&self, pattern: &str) -> HostScope<'_> {
+        HostScope::new(self, pattern)
+    }
+
+    /// Rejects targets longer than `max_uri_length`, authority included.
+    fn check_uri_length(&self, uri: &str) -> std::result::Result<(), ZapError> {
+        let max_length = self.max_uri_length.load(Ordering::SeqCst) as usize;
+        if uri.len() > max_length {
+            return Err(ZapError::uri_too_long(format!("URI exceeds {} bytes", max_length)));
+        }
+        Ok(())
+    }
+
+    /// Splits off the query string and applies path normalization.
+    fn prepare_path<'a>(&self, path: &'a str) -> std::result::Result<(String, Option<&'a str>), ZapError> {
+        self.check_uri_length(path)?;
+        let (_, path) = split_authority(path)?;
+        let (path, query) = match path.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (path, None),
+        };
+        let max_segments = self.max_path_segments.load(Ordering::SeqCst) as usize;
+        if trie::split_segments(trie::strip_root(path), self.keep_empty()).len() > max_segments {
+            return Err(ZapError::uri_too_long(format!("path exceeds {} segments", max_segments)));
+        }
+
+        if self.normalize_paths.load(Ordering::SeqCst) {
+            Ok((normalize_path(path)?, query))
+        } else {
+            Ok((path.to_string(), query))
+        }
+    }
+
+    fn keep_empty(&self) -> bool {
+        self.allow_empty_segments.load(Ordering::SeqCst)
+    }
+
+    fn find_handler(&self, method: &str, path: &str) -> std::result::Result<Option<HandlerInfo>, ZapError> {
+        self.check_uri_length(path)?;
+        if let (Some(authority), origin) = split_authority(path)? {
+            return self.find_handler_for_host(authority, method, origin);
+        }
+
+        validate_method(method).map_err(ZapError::bad_request)?;
+        let (path, query) = self.prepare_path(path)?;
+
+        let found = if let Ok(routes) = self.routes.lock() {
+            routes.find(method, &path, self.keep_empty())
+        } else {
+            None
+        };
+
+        let found = found.or_else(|| {
+            let catch_all = (*self.catch_all.lock().unwrap())?;
+            let mut params = RouteParams::new();
+            params.insert("*".to_string(), path.strip_prefix('/').unwrap_or(&path).to_string());
+            Some((catch_all, params))
+        });
+
+        Ok(found.map(|(id, mut params)| {
+            params.parse_query(query);
+            HandlerInfo { id, params }
+        }))
+    }
+
+    fn find_handler_for_host(&self, host: &str, method: &str, path: &str) -> std::result::Result<Option<HandlerInfo>, ZapError> {
+        self.check_uri_length(path)?;
+        let (authority, origin) = split_authority(path)?;
+        let host = authority.unwrap_or(host);
+        validate_method(method).map_err(ZapError::bad_request)?;
+        let (normalized, query) = self.prepare_path(origin)?;
+
+        if let Ok(host_routes) = self.host_routes.lock() {
+            for (pattern, routes) in host_routes.iter() {
+                if let Some(host_params) = scope::match_host(pattern, host) {
+                    if let Some((id, mut params)) = routes.find(method, &normalized, self.keep_empty()) {
+                        for (key, value) in host_params {
+                            params.insert(key, value);
+                        }
+                        params.parse_query(query);
+                        return Ok(Some(HandlerInfo { id, params }));
+                    }
+                }
+            }
+        }
+
+        self.find_handler(method, origin)
+    }
+
+    fn find_allowed_methods(&self, path: &str) -> std::result::Result<Vec<String>, ZapError> {
+        let (path, _) = self.prepare_path(path)?;
+        let routes = self.routes.lock().unwrap();
+        let mut methods = routes.methods();
+        methods.retain(|method| routes.find(method, &path, self.keep_empty()).is_some());
+        Ok(methods)
+    }
+
+    /// Resolves a request to a handler, or says why none matched. Errors
+    /// keep their kind, so an overlong URI is a 414 and a malformed method
+    /// or path a 400.
+    pub fn lookup(&self, method: &str, path: &str) -> std::result::Result<LookupResult, ZapError> {
+        let found = self.find_handler(method, path)?;
+        self.resolve(found, path)
+    }
+
+    /// Like [`Router::lookup`], for a request carrying a `Host` header.
+    pub fn lookup_for_host(&self, host: &str, method: &str, path: &str) -> std::result::Result<LookupResult, ZapError> {
+        let found = self.find_handler_for_host(host, method, path)?;
+        self.resolve(found, path)
+    }
+
+    fn resolve(&self, found: Option<HandlerInfo>, path: &str) -> std::result::Result<LookupResult, ZapError> {
+        if let Some(info) = found {
+            return Ok(LookupResult::Found(info));
+        }
+
+        let allowed = self.find_allowed_methods(path)?;
+        Ok(if allowed.is_empty() {
+            LookupResult::NotFound
+        } else {
+            LookupResult::MethodNotAllowed(allowed)
+        })
+    }
+
+    fn next_handler(&self, config: Option<RouteConfig>) -> HandlerId {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        if let Some(config) = config {
+            let mut configs = self.route_configs.lock().unwrap();
+            configs.insert(id, config);
+        }
+        id
+    }
+
+    fn register_route(&self, host: Option<&str>, method: &str, path: &str, config: Option<RouteConfig>) -> Result<HandlerId> {
+        validate_method(method).map_err(|reason| Error::new(Status::InvalidArg, reason))?;
+        validate_pattern(path).map_err(|reason| Error::new(Status::InvalidArg, reason))?;
+
+        let id = self.next_handler(config);
+        let keep_empty = self.keep_empty();
+        match host {
+            Some(host) => {
+                let mut host_routes = self.host_routes.lock().unwrap();
+                match host_routes.iter_mut().find(|(pattern, _)| pattern == host) {
+                    Some((_, routes)) => routes.insert(method, path, id, keep_empty),
+                    None => {
+                        let mut routes = RouteTable::new();
+                        routes.insert(method, path, id, keep_empty);
+                        host_routes.push((host.to_string(), routes));
+                    }
+                }
+            }
+            None => self.routes.lock().unwrap().insert(method, path, id, keep_empty),
+        }
+
+        self.registered.lock().unwrap().push(RegisteredRoute {
+            id,
+            method: method.to_string(),
+            path: path.to_string(),
+            host: host.map(str::to_string),
+        });
+
+        Ok(id)
+    }
+}
@@ -0,0 +1,115 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::str::FromStr;
+use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+use serde::de::{DeserializeOwned, Deserializer, Error as _, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+/// Deserializes a raw query string into `T`. Keys repeated in the query
+/// collect into sequence fields; scalar fields take the first value.
+/// Keys and values are percent-decoded, with `+` read as a space.
+pub(crate) fn deserialize<T: DeserializeOwned>(query: &str) -> Result<T, Error> {
+    let mut pairs: Vec<(Cow<str>, Vec<Cow<str>>)> = Vec::new();
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        match pairs.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, values)) => values.push(value),
+            None => pairs.push((key, vec![value])),
+        }
+    }
+
+    let entries = pairs.into_iter().map(|(key, values)| {
+        let values = Values { key: key.clone(), values };
+        (key, values)
+    });
+    T::deserialize(MapDeserializer::new(entries))
+}
+
+/// Every value given for one query key. The key is kept to name the field
+/// in parse errors.
+struct Values<'a> {
+    key: Cow<'a, str>,
+    values: Vec<Cow<'a, str>>,
+}
+
+impl<'a> Values<'a> {
+    fn first(&self) -> &str {
+        &self.values[0]
+    }
+
+    fn parse<T>(&self) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.first().parse().map_err(|e| Error::custom(format!("'{}' for '{}': {}", self.first(), self.key, e)))
+    }
+}
+
+impl<'a> IntoDeserializer<'a, Error> for Values<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Values<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.values.into_iter().next() {
+            Some(Cow::Borrowed(value)) => visitor.visit_borrowed_str(value),
+            Some(Cow::Owned(value)) => visitor.visit_string(value),
+            None => visitor.visit_borrowed_str(""),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let key = self.key;
+        let values = self.values.into_iter().map(move |value| Values { key: key.clone(), values: vec![value] });
+        visitor.visit_seq(SeqDeserializer::new(values))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.first().to_string().into_deserializer())
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 str string bytes byte_buf unit unit_struct newtype_struct
+        tuple tuple_struct map struct identifier ignored_any
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use napi::Result;
+use serde_json::Value;
+use super::{HandlerId, RouteConfig, Router};
+
+/// Registers several methods against a single path.
+pub struct RouteScope<'a> {
+    router: &'a Router,
+    path: String,
+    handler_ids: Vec<HandlerId>,
+}
+
+impl<'a> RouteScope<'a> {
+    pub(super) fn new(router: &'a Router, path: &str) -> Self {
+        Self {
+            router,
+            path: path.to_string(),
+            handler_ids: Vec::new(),
+        }
+    }
+
+    pub fn route(&mut self, method: &str, config: Option<RouteConfig>) -> Result<&mut Self> {
+        let id = self.router.register(method.to_string(), self.path.clone(), config)?;
+        self.handler_ids.push(id);
+        Ok(self)
+    }
+
+    pub fn get(&mut self, config: Option<RouteConfig>) -> Result<&mut Self> {
+        self.route("GET", config)
+    }
+
+    pub fn post(&mut self, config: Option<RouteConfig>) -> Result<&mut Self> {
+        self.route("POST", config)
+    }
+
+    pub fn put(&mut self, config: Option<RouteConfig>) -> Result<&mut Self> {
+        self.route("PUT", config)
+    }
+
+    pub fn delete(&mut self, config: Option<RouteConfig>) -> Result<&mut Self> {
+        self.route("DELETE", config)
+    }
+
+    pub fn patch(&mut self, config: Option<RouteConfig>) -> Result<&mut Self> {
+        self.route("PATCH", config)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Handler ids in registration order.
+    pub fn handler_ids(&self) -> &[HandlerId] {
+        &self.handler_ids
+    }
+}
+
+/// Registers routes that only match requests for a given host pattern.
+pub struct HostScope<'a> {
+    router: &'a Router,
+    pattern: String,
+}
+
+impl<'a> HostScope<'a> {
+    pub(super) fn new(router: &'a Router, pattern: &str) -> Self {
+        Self {
+            router,
+            pattern: pattern.to_string(),
+        }
+    }
+
+    pub fn register(&self, method: &str, path: &str, config: Option<RouteConfig>) -> Result<HandlerId> {
+        self.router.register_route(Some(&self.pattern), method, path, config)
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+}
+
+/// Matches a `Host` header value (port ignored) against a host pattern,
+/// returning the captured labels on success.
+pub(super) fn match_host(pattern: &str, host: &str) -> Option<Vec<(String, String)>> {
+    let host = strip_port(host);
+    let pattern_labels: Vec<&str> = pattern.split('.').collect();
+    let host_labels: Vec<&str> = host.split('.').collect();
+    if pattern_labels.len() != host_labels.len() {
+        return None;
+    }
+
+    let mut captures = Vec::new();
+    for (expected, actual) in pattern_labels.iter().zip(host_labels) {
+        if let Some(name) = expected.strip_prefix(':') {
+            captures.push((name.to_string(), actual.to_string()));
+        } else if !expected.eq_ignore_ascii_case(actual) {
+            return None;
+        }
+    }
+    Some(captures)
+}
+
+/// Drops a trailing `:port`, keeping bracketed IPv6 literals such as
+/// `[::1]` intact.
+fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        return host.find(']').map_or(host, |end| &host[..=end]);
+    }
+    host.split(':').next().unwrap_or(host)
+}
+
+/// Registers routes under a shared path prefix. Group guards run before any
+/// guards configured on the individual route.
+pub struct RouteGroup<'a> {
+    router: &'a Router,
+    prefix: String,
+    guards: Vec<u32>,
+    state: HashMap<String, Value>,
+}
+
+impl<'a> RouteGroup<'a> {
+    pub(super) fn new(router: &'a Router, prefix: &str) -> Self {
+        Self {
+            router,
+            prefix: prefix.trim_end_matches('/').to_string(),
+            guards: Vec::new(),
+            state: HashMap::new(),
+        }
+    }
+
+    /// Guards run before the route's own guards, in the order added.
+    pub fn guard(&mut self, guard_id: u32) -> &mut Self {
+        self.guards.push(guard_id);
+        self
+    }
+
+    /// Shares `value` with every route in the group through its `meta`.
+    /// A route's own `meta` entry for `key` takes precedence.
+    pub fn state(&mut self, key: &str, value: Value) -> &mut Self {
+        self.state.insert(key.to_string(), value);
+        self
+    }
+
+    pub fn register(&self, method: &str, path: &str, config: Option<RouteConfig>) -> Result<HandlerId> {
+        let full_path = format!("{}/{}", self.prefix, path.trim_start_matches('/'));
+        let mut config = config.unwrap_or_default();
+        config.guards = prepend_ids(&self.guards, config.guards.take());
+        if !self.state.is_empty() {
+            let mut meta = self.state.clone();
+            meta.extend(config.meta.take().unwrap_or_default());
+            config.meta = Some(meta);
+        }
+        self.router.register(method.to_string(), full_path, Some(config))
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+}
+
+/// Applies middleware to the routes registered through it, leaving their
+/// paths untouched. Router-level middleware runs first, then the scope's,
+/// then the route's own.
+pub struct Scope<'a> {
+    router: &'a Router,
+    middleware: Vec<u32>,
+}
+
+impl<'a> Scope<'a> {
+    pub(super) fn new(router: &'a Router) -> Self {
+        Self {
+            router,
+            middleware: Vec::new(),
+        }
+    }
+
+    pub fn with_middleware(&mut self, middleware_id: u32) -> &mut Self {
+        self.middleware.push(middleware_id);
+        self
+    }
+
+    pub fn register(&self, method: &str, path: &str, config: Option<RouteConfig>) -> Result<HandlerId> {
+        let mut config = config.unwrap_or_default();
+        config.middleware = prepend_ids(&self.middleware, config.middleware.take());
+        self.router.register(method.to_string(), path.to_string(), Some(config))
+    }
+}
+
+fn prepend_ids(scoped: &[u32], route: Option<Vec<u32>>) -> Option<Vec<u32>> {
+    if scoped.is_empty() {
+        return route;
+    }
+    let mut ids = scoped.to_vec();
+    ids.extend(route.unwrap_or_default());
+    Some(ids)
+}
@@ -1,23 +1,68 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use napi::{Result, sys, Env, NapiRaw};
+use napi_derive::napi;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use crate::error::ZapError;
+use super::query;
 use napi::bindgen_prelude::ToNapiValue;
 
 #[derive(Debug, Clone)]
 pub struct RouteParams {
     pub params: HashMap<String, String>,
+    pub query: HashMap<String, String>,
+    raw_query: Option<String>,
 }
 
 impl RouteParams {
     pub fn new() -> Self {
         Self {
             params: HashMap::new(),
+            query: HashMap::new(),
+            raw_query: None,
         }
     }
 
     pub fn insert(&mut self, key: String, value: String) {
         self.params.insert(key, value);
     }
+
+    /// Fills `query` from a raw query string. Keys and values are
+    /// percent-decoded; the first occurrence of a key wins.
+    pub fn parse_query(&mut self, query: Option<&str>) {
+        self.raw_query = query.map(str::to_string);
+        for (key, value) in form_urlencoded::parse(query.unwrap_or("").as_bytes()) {
+            self.query.entry(key.into_owned()).or_insert_with(|| value.into_owned());
+        }
+    }
+
+    /// Looks up `key` in the path params, then in the query.
+    pub fn any(&self, key: &str) -> Option<String> {
+        self.params.get(key)
+            .or_else(|| self.query.get(key))
+            .cloned()
+    }
+
+    pub fn require(&self, key: &str) -> std::result::Result<String, ZapError> {
+        self.any(key)
+            .ok_or_else(|| ZapError::bad_request(format!("missing parameter: {}", key)))
+    }
+
+    /// Deserializes the whole query string into `T`. Repeated keys fill
+    /// `Vec` fields; missing keys are only accepted for `Option` or
+    /// `#[serde(default)]` fields. Failures are 400s naming the field.
+    pub fn deserialize_query<T: DeserializeOwned>(&self) -> std::result::Result<T, ZapError> {
+        query::deserialize(self.raw_query.as_deref().unwrap_or(""))
+            .map_err(|error| ZapError::bad_request(format!("invalid query: {}", error)))
+    }
+
+    /// Serializes as `{"path": {...}, "query": {...}}` with keys sorted.
+    pub fn to_json(&self) -> Value {
+        let path: BTreeMap<_, _> = self.params.iter().collect();
+        let query: BTreeMap<_, _> = self.query.iter().collect();
+        json!({ "path": path, "query": query })
+    }
 }
 
 impl ToNapiValue for RouteParams {
@@ -53,15 +98,20 @@ impl TrieNode {
     }
 
     pub fn insert(&mut self, path: &str, handler_id: u32) {
+        self.insert_with(path, handler_id, false);
+    }
+
+    /// Inserts a route. With `keep_empty`, empty segments (as in `a//b` or a
+    /// trailing `/`) are edges of their own instead of being skipped.
+    pub fn insert_with(&mut self, path: &str, handler_id: u32, keep_empty: bool) {
         if path.is_empty() {
             self.handler_id = Some(handler_id);
             return;
         }
 
-        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
         let mut current = self;
 
-        for segment in segments {
+        for (_, segment) in split_segments(path, keep_empty) {
             if segment.starts_with(':') {
                 let param_name = segment[1..].to_string();
                 if current.param_child.is_none() {
@@ -102,45 +152,339 @@ impl TrieNode {
         current.handler_id = Some(handler_id);
     }
 
+    /// Number of endpoints at or below this node.
+    pub fn route_count(&self) -> usize {
+        self.handler_id.is_some() as usize
+            + self.child_nodes().map(|child| child.route_count()).sum::<usize>()
+    }
+
+    /// Number of nodes in this subtree, including this one.
+    pub fn node_count(&self) -> usize {
+        1 + self.child_nodes().map(|child| child.node_count()).sum::<usize>()
+    }
+
+    /// Renders the subtree as an indented tree, one edge per line. Static
+    /// edges are sorted; endpoints are marked with their handler id.
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        self.dump_into(&mut out, 0);
+        out
+    }
+
+    fn dump_into(&self, out: &mut String, depth: usize) {
+        for (edge, node) in self.edges() {
+            out.push_str(&"  ".repeat(depth));
+            out.push_str(&edge);
+            if let Some(id) = node.handler_id {
+                out.push_str(&format!(" [#{}]", id));
+            }
+            out.push('\n');
+            node.dump_into(out, depth + 1);
+        }
+    }
+
+    /// Outgoing edges labelled as in a pattern: sorted static segments, then
+    /// `:name`, then `*`.
+    fn edges(&self) -> Vec<(String, &TrieNode)> {
+        let mut edges: Vec<(String, &TrieNode)> = self.children.iter()
+            .map(|(segment, node)| (segment.clone(), node.as_ref()))
+            .collect();
+        edges.sort_by(|a, b| a.0.cmp(&b.0));
+        if let Some((name, node)) = &self.param_child {
+            edges.push((format!(":{}", name), node));
+        }
+        if let Some(node) = &self.wildcard_child {
+            edges.push(("*".to_string(), node));
+        }
+        edges
+    }
+
+    fn child_nodes(&self) -> impl Iterator<Item = &TrieNode> {
+        self.children.values()
+            .chain(self.param_child.as_ref().map(|(_, node)| node))
+            .chain(self.wildcard_child.as_ref())
+            .map(|node| node.as_ref())
+    }
+
+    /// Finds the most specific route for `path`. Routes with more static
+    /// segments win; ties are broken position by position, preferring
+    /// static over param over wildcard segments.
+    ///
+    /// A wildcard captures the rest of `path` exactly as given, including
+    /// empty segments and percent-encoding.
     pub fn find(&self, path: &str) -> Option<(u32, RouteParams)> {
-        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        self.find_with(path, false)
+    }
+
+    /// Like [`find`](Self::find), matching empty segments as edges when
+    /// `keep_empty` is set. Must agree with the mode routes were inserted in.
+    pub fn find_with(&self, path: &str, keep_empty: bool) -> Option<(u32, RouteParams)> {
+        let segments = split_segments(path, keep_empty);
+        self.find_best(path, &segments).map(|m| (m.handler_id, m.params))
+    }
+
+    fn find_best(&self, path: &str, segments: &[(usize, &str)]) -> Option<Match> {
         let mut params = RouteParams::new();
-        self.find_internal(&segments, &mut params)
+        let mut kinds = Vec::with_capacity(segments.len());
+        let mut best = None;
+        self.find_internal(path, segments, &mut params, &mut kinds, &mut best);
+        best
+    }
+
+    /// Walks `path` through the trie, recording the edge taken for each
+    /// segment. On a match the steps follow the winning route; otherwise
+    /// they follow static, then param, then wildcard edges as far as
+    /// possible and `reason` says where the walk stopped.
+    pub fn explain(&self, path: &str, keep_empty: bool) -> MatchExplanation {
+        let segments = split_segments(path, keep_empty);
+        let best = self.find_best(path, &segments);
+        let mut steps = Vec::new();
+        let mut node = self;
+
+        for (i, &(offset, segment)) in segments.iter().enumerate() {
+            let kind = match &best {
+                Some(m) => Some(m.specificity.kinds[i]),
+                None if node.children.contains_key(segment) => Some(SegmentKind::Static),
+                None if node.param_child.is_some() && !segment.is_empty() => Some(SegmentKind::Param),
+                None if node.wildcard_child.is_some() => Some(SegmentKind::Wildcard),
+                None => None,
+            };
+            let alternatives = node.edges().into_iter().map(|(edge, _)| edge).collect();
+            let (edge, next) = match kind {
+                Some(SegmentKind::Static) => (segment.to_string(), &node.children[segment]),
+                Some(SegmentKind::Param) => {
+                    let (name, child) = node.param_child.as_ref().unwrap();
+                    (format!(":{}", name), child)
+                }
+                Some(SegmentKind::Wildcard) => ("*".to_string(), node.wildcard_child.as_ref().unwrap()),
+                None => {
+                    steps.push(MatchStep { segment: segment.to_string(), edge: None, alternatives });
+                    return MatchExplanation {
+                        handler_id: None,
+                        steps,
+                        reason: Some(format!("no edge matches segment '{}'", segment)),
+                    };
+                }
+            };
+
+            let is_wildcard = kind == Some(SegmentKind::Wildcard);
+            let segment = if is_wildcard { &path[offset..] } else { segment };
+            steps.push(MatchStep { segment: segment.to_string(), edge: Some(edge), alternatives });
+            node = next;
+            if is_wildcard {
+                break;
+            }
+        }
+
+        let handler_id = best.map(|m| m.handler_id);
+        let reason = match handler_id {
+            Some(_) => None,
+            None => Some("path ends at a node without a handler".to_string()),
+        };
+        MatchExplanation { handler_id, steps, reason }
     }
 
-    fn find_internal(&self, segments: &[&str], params: &mut RouteParams) -> Option<(u32, RouteParams)> {
+    fn find_internal(
+        &self,
+        path: &str,
+        segments: &[(usize, &str)],
+        params: &mut RouteParams,
+        kinds: &mut Vec<SegmentKind>,
+        best: &mut Option<Match>,
+    ) {
         if segments.is_empty() {
-            return self.handler_id.map(|id| (id, params.clone()));
+            if let Some(handler_id) = self.handler_id {
+                let specificity = Specificity::new(kinds);
+                if best.as_ref().is_none_or(|b| specificity > b.specificity) {
+                    *best = Some(Match {
+                        handler_id,
+                        params: params.clone(),
+                        specificity,
+                    });
+                }
+            }
+            return;
         }
 
-        let segment = segments[0];
+        let (offset, segment) = segments[0];
         let remaining = &segments[1..];
 
-        // Try exact match first
+        // Try exact match
         if let Some(child) = self.children.get(segment) {
-            if let Some(result) = child.find_internal(remaining, params) {
-                return Some(result);
-            }
+            kinds.push(SegmentKind::Static);
+            child.find_internal(path, remaining, params, kinds, best);
+            kinds.pop();
         }
 
-        // Try parameter match
-        if let Some((param_name, child)) = &self.param_child {
-            let mut new_params = params.clone();
-            new_params.insert(param_name.clone(), segment.to_string());
-            if let Some(result) = child.find_internal(remaining, &mut new_params) {
-                return Some(result);
-            }
+        // Try parameter match; params never capture an empty segment
+        if let Some((param_name, child)) = self.param_child.as_ref().filter(|_| !segment.is_empty()) {
+            let previous = params.params.insert(param_name.clone(), segment.to_string());
+            kinds.push(SegmentKind::Param);
+            child.find_internal(path, remaining, params, kinds, best);
+            kinds.pop();
+            restore(params, param_name, previous);
         }
 
         // Try wildcard match
         if let Some(child) = &self.wildcard_child {
-            let mut new_params = params.clone();
-            new_params.insert("*".to_string(), segments.join("/"));
-            if let Some(result) = child.find_internal(&[], &mut new_params) {
-                return Some(result);
+            let previous = params.params.insert("*".to_string(), path[offset..].to_string());
+            kinds.push(SegmentKind::Wildcard);
+            child.find_internal(path, &[], params, kinds, best);
+            kinds.pop();
+            restore(params, "*", previous);
+        }
+    }
+}
+
+/// Routes keyed by method, one trie per method. Paths are matched with
+/// their leading `/` removed.
+#[derive(Debug, Clone, Default)]
+pub struct RouteTable {
+    methods: HashMap<String, TrieNode>,
+}
+
+impl RouteTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, method: &str, path: &str, handler_id: u32, keep_empty: bool) {
+        self.methods.entry(method.to_string())
+            .or_insert_with(TrieNode::new)
+            .insert_with(strip_root(path), handler_id, keep_empty);
+    }
+
+    pub fn find(&self, method: &str, path: &str, keep_empty: bool) -> Option<(u32, RouteParams)> {
+        self.methods.get(method)?.find_with(strip_root(path), keep_empty)
+    }
+
+    /// Registered methods, sorted.
+    pub fn methods(&self) -> Vec<String> {
+        let mut methods: Vec<String> = self.methods.keys().cloned().collect();
+        methods.sort();
+        methods
+    }
+
+    pub fn route_count(&self) -> usize {
+        self.methods.values().map(TrieNode::route_count).sum()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.methods.values().map(TrieNode::node_count).sum()
+    }
+
+    /// Dumps each method's trie under a line naming the method.
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        for method in self.methods() {
+            let trie = &self.methods[&method];
+            out.push_str(&method);
+            if let Some(id) = trie.handler_id {
+                out.push_str(&format!(" [#{}]", id));
             }
+            out.push('\n');
+            trie.dump_into(&mut out, 1);
         }
+        out
+    }
+
+    /// Explains a lookup. The first step is the method.
+    pub fn explain(&self, method: &str, path: &str, keep_empty: bool) -> MatchExplanation {
+        let alternatives = self.methods();
+        let Some(trie) = self.methods.get(method) else {
+            return MatchExplanation {
+                handler_id: None,
+                steps: vec![MatchStep { segment: method.to_string(), edge: None, alternatives }],
+                reason: Some(format!("no routes for method '{}'", method)),
+            };
+        };
 
-        None
+        let mut explanation = trie.explain(strip_root(path), keep_empty);
+        explanation.steps.insert(0, MatchStep {
+            segment: method.to_string(),
+            edge: Some(method.to_string()),
+            alternatives,
+        });
+        explanation
     }
-} 
\ No newline at end of file
+}
+
+pub(super) fn strip_root(path: &str) -> &str {
+    path.strip_prefix('/').unwrap_or(path)
+}
+
+/// Step-by-step account of a lookup, from [`TrieNode::explain`].
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct MatchExplanation {
+    /// Handler the lookup resolves to, if any.
+    pub handler_id: Option<u32>,
+    pub steps: Vec<MatchStep>,
+    /// Why the lookup failed; `None` on a match.
+    pub reason: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Debug, Clone)]
+pub struct MatchStep {
+    /// The segment consumed; for a wildcard, the whole remainder.
+    pub segment: String,
+    /// Edge taken, labelled as in a pattern. `None` where matching failed.
+    pub edge: Option<String>,
+    /// Every edge available at this point.
+    pub alternatives: Vec<String>,
+}
+
+/// Undoes a param binding when backtracking out of a branch.
+fn restore(params: &mut RouteParams, key: &str, previous: Option<String>) {
+    match previous {
+        Some(value) => params.params.insert(key.to_string(), value),
+        None => params.params.remove(key),
+    };
+}
+
+/// Splits `path` on `/`, pairing each segment with its byte offset.
+pub(super) fn split_segments(path: &str, keep_empty: bool) -> Vec<(usize, &str)> {
+    let mut segments = Vec::new();
+    if path.is_empty() {
+        return segments;
+    }
+    let mut offset = 0;
+    for segment in path.split('/') {
+        if keep_empty || !segment.is_empty() {
+            segments.push((offset, segment));
+        }
+        offset += segment.len() + 1;
+    }
+    segments
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum SegmentKind {
+    Wildcard,
+    Param,
+    Static,
+}
+
+/// Ordering key for competing matches: static segment count first, then
+/// the per-position segment kinds.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Specificity {
+    static_count: usize,
+    kinds: Vec<SegmentKind>,
+}
+
+impl Specificity {
+    fn new(kinds: &[SegmentKind]) -> Self {
+        Self {
+            static_count: kinds.iter().filter(|k| **k == SegmentKind::Static).count(),
+            kinds: kinds.to_vec(),
+        }
+    }
+}
+
+struct Match {
+    handler_id: u32,
+    params: RouteParams,
+    specificity: Specificity,
+}
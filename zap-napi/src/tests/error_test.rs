@@ -0,0 +1,90 @@
+use serde_json::json;
+use crate::{FieldError, ZapError};
+
+#[test]
+fn test_validation_error_renders_422() {
+    let error = ZapError::validation("Validation failed", vec![
+        FieldError {
+            field: "email".into(),
+            code: "invalid_email".into(),
+            message: "Invalid email format".into(),
+        },
+    ]);
+
+    assert_eq!(error.status(), 422);
+    assert_eq!(error.to_json(), json!({
+        "errors": [
+            { "field": "email", "code": "invalid_email", "message": "Invalid email format" }
+        ]
+    }));
+
+    // Hooks clone errors before handing them to JS
+    let cloned = error.clone();
+    assert_eq!(cloned.errors.len(), 1);
+}
+
+/// One case per `ErrorKind`.
+#[test]
+fn test_error_classification() {
+    let cases = [
+        (ZapError::not_found("missing"), true, false),
+        (ZapError::bad_request("bad"), true, false),
+        (ZapError::forbidden("denied"), true, false),
+        (ZapError::validation_error("invalid", None), true, false),
+        (ZapError::internal("boom"), false, false),
+        (ZapError::method_not_allowed("nope", &["GET".to_string()]), true, false),
+        (ZapError::not_acceptable("v9"), true, false),
+        (ZapError::uri_too_long("long"), true, false),
+        (ZapError::too_many_requests("slow down"), true, true),
+        (ZapError::service_unavailable("overloaded"), false, true),
+        (ZapError::gateway_timeout("slow upstream"), false, true),
+    ];
+
+    for (error, client_error, retryable) in cases {
+        assert_eq!(error.is_client_error(), client_error, "{}", error);
+        assert_eq!(error.is_retryable(), retryable, "{}", error);
+    }
+}
+
+#[test]
+fn test_error_detail_serialization() {
+    let not_found = ZapError::not_found("no route for /users");
+    assert_eq!(serde_json::to_value(not_found.to_detail()).unwrap(), json!({
+        "code": "not_found",
+        "message": "no route for /users",
+        "status": 404,
+        "details": null,
+    }));
+
+    let not_allowed = ZapError::method_not_allowed("POST not allowed", &["GET".to_string(), "HEAD".to_string()]);
+    assert_eq!(not_allowed.to_json(), json!({
+        "error": {
+            "code": "method_not_allowed",
+            "message": "POST not allowed",
+            "status": 405,
+            "details": "GET, HEAD",
+        }
+    }));
+
+    let invalid = ZapError::validation_error("Invalid body", Some("email is required".into()));
+    let detail = invalid.to_detail();
+    assert_eq!(detail.code, "validation_error");
+    assert_eq!(detail.status, 422);
+    assert_eq!(detail.details.as_deref(), Some("email is required"));
+}
+
+#[test]
+fn test_error_source_chain() {
+    use std::error::Error;
+
+    let io = std::io::Error::new(std::io::ErrorKind::TimedOut, "db timeout");
+    let error = ZapError::internal_with_source("query failed", io);
+    assert_eq!(error.status(), 500);
+    assert_eq!(error.source().unwrap().to_string(), "db timeout");
+
+    let cloned = error.clone();
+    let source = cloned.source().unwrap().downcast_ref::<std::io::Error>().unwrap();
+    assert_eq!(source.kind(), std::io::ErrorKind::TimedOut);
+
+    assert!(ZapError::internal("boom").source().is_none());
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use crate::error::ErrorKind;
+use crate::extract::{content_length, content_type, header, negotiate_locale, require_header};
+use crate::types::JsRequest;
+
+fn request(headers: &[(&str, &str)]) -> JsRequest {
+    JsRequest {
+        headers: headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), vec![value.to_string()]))
+            .collect::<HashMap<_, _>>(),
+        ..JsRequest::new("GET", "/")
+    }
+}
+
+#[test]
+fn test_header_present() {
+    let req = request(&[("X-Page", " 3 "), ("Content-Length", "42"), ("Content-Type", "application/json; charset=utf-8")]);
+
+    assert_eq!(header::<u32>(&req, "x-page").unwrap(), Some(3));
+    assert_eq!(require_header::<u32>(&req, "X-Page").unwrap(), 3);
+    assert_eq!(content_length(&req).unwrap(), Some(42));
+
+    let mime = content_type(&req).unwrap().unwrap();
+    assert_eq!(mime.essence_str(), "application/json");
+    assert_eq!(mime.get_param(mime::CHARSET).unwrap(), "utf-8");
+}
+
+#[test]
+fn test_header_absent() {
+    let req = request(&[]);
+
+    assert_eq!(header::<u32>(&req, "x-page").unwrap(), None);
+    assert_eq!(content_type(&req).unwrap(), None);
+
+    let error = require_header::<u32>(&req, "x-page").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::BadRequest);
+    assert!(error.message.contains("x-page"));
+}
+
+#[test]
+fn test_header_unparseable() {
+    let req = request(&[("X-Page", "two"), ("Content-Length", "-1"), ("Content-Type", "json")]);
+
+    let error = header::<u32>(&req, "x-page").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::BadRequest);
+    assert_eq!(error.status(), 400);
+    assert!(error.message.contains("x-page"));
+    assert!(error.message.contains("two"));
+
+    assert_eq!(content_length(&req).unwrap_err().kind, ErrorKind::BadRequest);
+    assert_eq!(content_type(&req).unwrap_err().kind, ErrorKind::BadRequest);
+    assert_eq!(require_header::<u32>(&req, "x-page").unwrap_err().kind, ErrorKind::BadRequest);
+}
+
+#[test]
+fn test_negotiate_locale() {
+    let req = request(&[("Accept-Language", "en-US,en;q=0.9,fr;q=0.5")]);
+
+    assert_eq!(negotiate_locale(&req, &["fr", "en-US", "en"], "de"), "en-US");
+    assert_eq!(negotiate_locale(&req, &["fr", "en"], "de"), "en");
+    assert_eq!(negotiate_locale(&req, &["fr", "en-GB"], "de"), "en-GB");
+    assert_eq!(negotiate_locale(&req, &["fr", "de"], "de"), "fr");
+    assert_eq!(negotiate_locale(&req, &["de", "es"], "de"), "de");
+    assert_eq!(negotiate_locale(&request(&[]), &["fr", "en"], "en"), "en");
+
+    let weighted = request(&[("Accept-Language", "fr;q=0, de;q=0.3, *, en;q=0.8")]);
+    assert_eq!(negotiate_locale(&weighted, &["fr", "de", "en"], "es"), "en");
+    assert_eq!(negotiate_locale(&weighted, &["fr"], "es"), "es");
+}
@@ -0,0 +1,5 @@
+mod error_test;
+mod router_test;
+mod trie_test;
+mod integration_test;
+mod extract_test;
@@ -0,0 +1,509 @@
+use crate::hooks::Hooks;
+use crate::{Deprecation, ErrorKind, LookupResult, Router, RouteConfig, RouteDefinition};
+use serde_json::json;
+
+fn route(method: &str, path: &str) -> RouteDefinition {
+    RouteDefinition {
+        method: method.into(),
+        path: path.into(),
+        config: None,
+    }
+}
+
+#[test]
+fn test_register_all() {
+    let router = Router::new(Hooks::new());
+
+    let ids = router.register_all(vec![
+        route("GET", "/users"),
+        route("POST", "/users"),
+        route("GET", "/users/:id"),
+        route("DELETE", "/users/:id"),
+        route("GET", "/files/*"),
+    ]).unwrap();
+    assert_eq!(ids.len(), 5);
+
+    let lookups = [
+        ("GET", "/users"),
+        ("POST", "/users"),
+        ("GET", "/users/1"),
+        ("DELETE", "/users/1"),
+        ("GET", "/files/a/b.txt"),
+    ];
+    for (id, (method, path)) in ids.iter().zip(lookups) {
+        let info = router.get_handler_info(method.into(), path.into())
+            .unwrap()
+            .expect("Should match registered route");
+        assert_eq!(info.id, *id);
+    }
+}
+
+#[test]
+fn test_register_all_reports_invalid_index() {
+    let router = Router::new(Hooks::new());
+
+    let error = router.register_all(vec![
+        route("GET", "/a"),
+        route("GET", "/b"),
+        route("GET", "/c/*/d"),
+    ]).unwrap_err();
+    assert!(error.reason.starts_with("route 2:"));
+
+    // Nothing from the failed batch was registered
+    assert!(router.get_handler_info("GET".into(), "/a".into()).unwrap().is_none());
+}
+
+#[test]
+fn test_route_scope() {
+    let router = Router::new(Hooks::new());
+
+    let mut scope = router.at("/users");
+    scope.get(None).unwrap().post(None).unwrap();
+    let ids = scope.handler_ids().to_vec();
+    assert_eq!(ids.len(), 2);
+
+    let get = router.get_handler_info("GET".into(), "/users".into()).unwrap().unwrap();
+    let post = router.get_handler_info("POST".into(), "/users".into()).unwrap().unwrap();
+    assert_eq!(get.id, ids[0]);
+    assert_eq!(post.id, ids[1]);
+}
+
+#[test]
+fn test_path_normalization() {
+    let router = Router::new(Hooks::new());
+    let id = router.register("GET".into(), "/users/:id".into(), None).unwrap();
+    let c_id = router.register("GET".into(), "/a/c".into(), None).unwrap();
+    router.set_path_normalization(true);
+
+    let info = router.get_handler_info("GET".into(), "/users//123".into()).unwrap().unwrap();
+    assert_eq!(info.id, id);
+    assert_eq!(info.params.params.get("id").unwrap(), "123");
+
+    let info = router.get_handler_info("GET".into(), "/a/./b/../c".into()).unwrap().unwrap();
+    assert_eq!(info.id, c_id);
+
+    let error = router.get_handler_info("GET".into(), "/a/../../etc/passwd".into()).unwrap_err();
+    assert!(error.reason.starts_with("Bad Request"));
+}
+
+#[test]
+fn test_host_scopes() {
+    let router = Router::new(Hooks::new());
+    let dashboard = router.host(":tenant.example.com")
+        .register("GET", "/dashboard", None)
+        .unwrap();
+    let status = router.host("api.other.com")
+        .register("GET", "/status", None)
+        .unwrap();
+
+    let info = router.get_handler_info_for_host("acme.example.com:8080".into(), "GET".into(), "/dashboard".into())
+        .unwrap()
+        .expect("Should match tenant host");
+    assert_eq!(info.id, dashboard);
+    assert_eq!(info.params.params.get("tenant").unwrap(), "acme");
+
+    let info = router.get_handler_info_for_host("api.other.com".into(), "GET".into(), "/status".into())
+        .unwrap()
+        .expect("Should match api host");
+    assert_eq!(info.id, status);
+
+    assert!(router.get_handler_info_for_host("api.other.com".into(), "GET".into(), "/dashboard".into()).unwrap().is_none());
+    assert!(router.get_handler_info_for_host("acme.example.com".into(), "GET".into(), "/status".into()).unwrap().is_none());
+    assert!(router.get_handler_info("GET".into(), "/dashboard".into()).unwrap().is_none());
+}
+
+#[test]
+fn test_register_for_host() {
+    let router = Router::new(Hooks::new());
+    let api = router.register_for_host(":tenant.example.com".into(), "GET".into(), "/status".into(), None).unwrap();
+    let local = router.register_for_host("[::1]".into(), "GET".into(), "/status".into(), None).unwrap();
+
+    let info = router.get_handler_info_for_host("acme.example.com".into(), "GET".into(), "/status".into())
+        .unwrap()
+        .unwrap();
+    assert_eq!(info.id, api);
+    assert_eq!(info.params.params.get("tenant").unwrap(), "acme");
+
+    for host in ["[::1]", "[::1]:8080"] {
+        let info = router.get_handler_info_for_host(host.into(), "GET".into(), "/status".into())
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.id, local);
+    }
+    assert!(router.get_handler_info_for_host("[::2]:8080".into(), "GET".into(), "/status".into()).unwrap().is_none());
+}
+
+#[test]
+fn test_route_timeout_overrides_global() {
+    let router = Router::new(Hooks::new());
+    router.set_handler_timeout(Some(100));
+
+    let report = router.register("GET".into(), "/reports".into(), Some(RouteConfig {
+        timeout_ms: Some(30_000),
+        ..Default::default()
+    })).unwrap();
+    let users = router.register("GET".into(), "/users".into(), None).unwrap();
+
+    assert_eq!(router.get_timeout(report), Some(30_000));
+    assert_eq!(router.get_timeout(users), Some(100));
+
+    router.set_handler_timeout(None);
+    assert_eq!(router.get_timeout(users), None);
+}
+
+#[test]
+fn test_params_from_path_or_query() {
+    let router = Router::new(Hooks::new());
+    router.register("GET".into(), "/items/:id".into(), None).unwrap();
+    router.register("GET".into(), "/items".into(), None).unwrap();
+
+    let info = router.get_handler_info("GET".into(), "/items/7".into()).unwrap().unwrap();
+    assert_eq!(info.params.any("id").unwrap(), "7");
+
+    let info = router.get_handler_info("GET".into(), "/items?id=5&sort=asc".into()).unwrap().unwrap();
+    assert_eq!(info.params.any("id").unwrap(), "5");
+    assert_eq!(info.params.require("sort").unwrap(), "asc");
+
+    let error = info.params.require("page").unwrap_err();
+    assert_eq!(error.status(), 400);
+}
+
+#[test]
+fn test_group_guards_apply_to_group_routes() {
+    let router = Router::new(Hooks::new());
+    let admin_guard = 42;
+    let audit_guard = 43;
+
+    let mut admin = router.group("/admin");
+    admin.guard(admin_guard);
+    let dashboard = admin.register("GET", "/dashboard", None).unwrap();
+    let users = admin.register("GET", "/users", Some(RouteConfig {
+        guards: Some(vec![audit_guard]),
+        ..Default::default()
+    })).unwrap();
+    let public = router.register("GET".into(), "/dashboard".into(), None).unwrap();
+
+    let info = router.get_handler_info("GET".into(), "/admin/dashboard".into()).unwrap().unwrap();
+    assert_eq!(info.id, dashboard);
+    assert_eq!(router.get_guard_ids(dashboard), vec![admin_guard]);
+    assert_eq!(router.get_guard_ids(users), vec![admin_guard, audit_guard]);
+    assert!(router.get_guard_ids(public).is_empty());
+}
+
+#[test]
+fn test_describe_conflicts() {
+    let router = Router::new(Hooks::new());
+    router.register("GET".into(), "/files/:name".into(), None).unwrap();
+    router.register("GET".into(), "/files/:id".into(), None).unwrap();
+    router.register("GET".into(), "/users/:id".into(), None).unwrap();
+    router.register("GET".into(), "/users/:userId/posts".into(), None).unwrap();
+    router.register("POST".into(), "/files/:name".into(), None).unwrap();
+    router.register("GET".into(), "/assets/*".into(), None).unwrap();
+    router.register("GET".into(), "/assets/logo.png".into(), None).unwrap();
+
+    let conflicts = router.describe_conflicts();
+    assert_eq!(conflicts.len(), 2);
+
+    assert_eq!(conflicts[0].route, "/files/:name");
+    assert_eq!(conflicts[0].conflicts_with, "/files/:id");
+
+    assert_eq!(conflicts[1].route, "/users/:userId/posts");
+    assert_eq!(conflicts[1].conflicts_with, "/users/:id");
+}
+
+#[test]
+fn test_describe_conflicts_with_empty_segments() {
+    let router = Router::new(Hooks::new());
+    router.register("GET".into(), "/a/b".into(), None).unwrap();
+    router.register("GET".into(), "/a//b".into(), None).unwrap();
+    assert_eq!(router.describe_conflicts().len(), 1);
+
+    let router = Router::new(Hooks::new());
+    router.set_allow_empty_segments(true);
+    let single = router.register("GET".into(), "/a/b".into(), None).unwrap();
+    let double = router.register("GET".into(), "/a//b".into(), None).unwrap();
+    assert!(router.describe_conflicts().is_empty());
+    assert_eq!(router.get_handler_info("GET".into(), "/a/b".into()).unwrap().unwrap().id, single);
+    assert_eq!(router.get_handler_info("GET".into(), "/a//b".into()).unwrap().unwrap().id, double);
+}
+
+#[test]
+fn test_scope_middleware_only_on_scoped_routes() {
+    let router = Router::new(Hooks::new());
+    let skip_auth = 7;
+    let logging = 8;
+
+    let mut public = router.scope();
+    public.with_middleware(skip_auth);
+    let login = public.register("POST", "/login", None).unwrap();
+    let health = public.register("GET", "/health", Some(RouteConfig {
+        middleware: Some(vec![logging]),
+        ..Default::default()
+    })).unwrap();
+    let profile = router.register("GET".into(), "/profile".into(), None).unwrap();
+
+    let info = router.get_handler_info("POST".into(), "/login".into()).unwrap().unwrap();
+    assert_eq!(info.id, login);
+    assert_eq!(router.get_middleware_ids(login), vec![skip_auth]);
+    assert_eq!(router.get_middleware_ids(health), vec![skip_auth, logging]);
+    assert!(router.get_middleware_ids(profile).is_empty());
+}
+
+#[test]
+fn test_catch_all() {
+    let router = Router::new(Hooks::new());
+    let users = router.register("GET".into(), "/users".into(), None).unwrap();
+    assert!(router.get_handler_info("DELETE".into(), "/anything".into()).unwrap().is_none());
+
+    let proxy = router.catch_all(None).unwrap();
+
+    for (method, path, rest) in [
+        ("GET", "/anything", "anything"),
+        ("POST", "/users", "users"),
+        ("PROPFIND", "/dav/a/b", "dav/a/b"),
+        ("GET", "//cdn/a", "/cdn/a"),
+    ] {
+        let info = router.get_handler_info(method.into(), path.into()).unwrap().unwrap();
+        assert_eq!(info.id, proxy);
+        assert_eq!(info.params.params.get("*").unwrap(), rest);
+    }
+
+    let info = router.get_handler_info("GET".into(), "/users".into()).unwrap().unwrap();
+    assert_eq!(info.id, users);
+}
+
+#[test]
+fn test_deprecation_headers() {
+    let router = Router::new(Hooks::new());
+    let legacy = router.register("GET".into(), "/v1/users".into(), Some(RouteConfig {
+        deprecation: Some(Deprecation {
+            sunset: Some("Sat, 01 Nov 2025 00:00:00 GMT".into()),
+        }),
+        ..Default::default()
+    })).unwrap();
+    let current = router.register("GET".into(), "/v2/users".into(), None).unwrap();
+
+    let headers = router.get_deprecation_headers(legacy).expect("Should be deprecated");
+    assert_eq!(headers.get("Deprecation").unwrap(), "true");
+    assert_eq!(headers.get("Sunset").unwrap(), "Sat, 01 Nov 2025 00:00:00 GMT");
+    assert!(router.get_deprecation_headers(current).is_none());
+}
+
+#[test]
+fn test_error_handlers_per_kind() {
+    let router = Router::new(Hooks::new());
+    let not_found = router.on_error(ErrorKind::NotFound, None).unwrap();
+    let internal = router.on_error(ErrorKind::InternalError, None).unwrap();
+
+    assert_ne!(not_found, internal);
+    assert_eq!(router.get_error_handler(ErrorKind::NotFound), Some(not_found));
+    assert_eq!(router.get_error_handler(ErrorKind::InternalError), Some(internal));
+    assert_eq!(router.get_error_handler(ErrorKind::BadRequest), None);
+}
+
+#[test]
+fn test_extension_methods() {
+    let router = Router::new(Hooks::new());
+    let propfind = router.register("PROPFIND".into(), "/dav/:file".into(), None).unwrap();
+
+    let info = router.get_handler_info("PROPFIND".into(), "/dav/notes.txt".into()).unwrap().unwrap();
+    assert_eq!(info.id, propfind);
+    assert!(router.get_handler_info("GET".into(), "/dav/notes.txt".into()).unwrap().is_none());
+
+    assert!(router.register("GET/dav".into(), "/x".into(), None).is_err());
+    let error = router.get_handler_info("PROP FIND".into(), "/dav/notes.txt".into()).unwrap_err();
+    assert!(error.reason.starts_with("Bad Request"));
+}
+
+#[test]
+fn test_lookup_distinguishes_not_found_and_method_not_allowed() {
+    let router = Router::new(Hooks::new());
+    let get = router.register("GET".into(), "/users/:id".into(), None).unwrap();
+    router.register("PUT".into(), "/users/:id".into(), None).unwrap();
+    router.register("POST".into(), "/users".into(), None).unwrap();
+
+    match router.lookup("GET", "/users/1").unwrap() {
+        LookupResult::Found(info) => assert_eq!(info.id, get),
+        other => panic!("expected Found, got {:?}", other),
+    }
+
+    match router.lookup("DELETE", "/users/1").unwrap() {
+        LookupResult::MethodNotAllowed(allowed) => assert_eq!(allowed, vec!["GET", "PUT"]),
+        other => panic!("expected MethodNotAllowed, got {:?}", other),
+    }
+
+    assert!(matches!(router.lookup("GET", "/missing").unwrap(), LookupResult::NotFound));
+}
+
+#[test]
+fn test_uri_limits() {
+    let router = Router::new(Hooks::new());
+    router.register("GET".into(), "/files/*".into(), None).unwrap();
+    router.set_max_uri_length(32);
+    router.set_max_path_segments(4);
+
+    let under = format!("/files/{}", "a".repeat(25));
+    assert_eq!(under.len(), 32);
+    assert!(router.get_handler_info("GET".into(), under).unwrap().is_some());
+
+    let over = format!("/files/{}", "a".repeat(26));
+    let error = router.get_handler_info("GET".into(), over).unwrap_err();
+    assert!(error.reason.starts_with("URI Too Long"));
+
+    assert!(router.get_handler_info("GET".into(), "/files/a/b/c".into()).unwrap().is_some());
+    let error = router.get_handler_info("GET".into(), "/files/a/b/c/d".into()).unwrap_err();
+    assert!(error.reason.starts_with("URI Too Long"));
+
+    // Empty segments count once they are significant
+    router.set_allow_empty_segments(true);
+    assert!(router.get_handler_info("GET".into(), "/files/a".into()).unwrap().is_some());
+    let error = router.get_handler_info("GET".into(), "/files/////".into()).unwrap_err();
+    assert!(error.reason.starts_with("URI Too Long"));
+    router.set_allow_empty_segments(false);
+
+    // The authority of an absolute-form target counts towards the limit
+    let absolute = format!("http://example.com/files/{}", "a".repeat(10));
+    assert!(absolute.len() > 32);
+    let error = router.get_handler_info("GET".into(), absolute.clone()).unwrap_err();
+    assert!(error.reason.starts_with("URI Too Long"));
+    let error = router.get_handler_info_for_host("example.com".into(), "GET".into(), absolute).unwrap_err();
+    assert!(error.reason.starts_with("URI Too Long"));
+}
+
+#[test]
+fn test_route_and_node_counts() {
+    let router = Router::new(Hooks::new());
+    assert_eq!(router.route_count(), 0);
+    assert_eq!(router.trie_node_count(), 0);
+
+    router.register("GET".into(), "/users".into(), None).unwrap();
+    router.register("GET".into(), "/users/:id".into(), None).unwrap();
+    router.register("POST".into(), "/users".into(), None).unwrap();
+    router.register("GET".into(), "/files/*".into(), None).unwrap();
+    // Replacing a route does not add one
+    router.register("GET".into(), "/users".into(), None).unwrap();
+    router.host("api.example.com").register("GET", "/status", None).unwrap();
+
+    assert_eq!(router.route_count(), 5);
+    // GET root, users, :id, files, * + POST root, users + host GET root, status
+    assert_eq!(router.trie_node_count(), 9);
+}
+
+#[test]
+fn test_absolute_form_targets() {
+    let router = Router::new(Hooks::new());
+    let users = router.register("GET".into(), "/users/:id".into(), None).unwrap();
+    let api = router.host("api.example.com").register("GET", "/users/:id", None).unwrap();
+
+    let info = router.get_handler_info("GET".into(), "http://www.example.com/users/7?full=1".into())
+        .unwrap()
+        .unwrap();
+    assert_eq!(info.id, users);
+    assert_eq!(info.params.params.get("id").unwrap(), "7");
+    assert_eq!(info.params.query.get("full").unwrap(), "1");
+
+    // The authority selects host-scoped routes, overriding the Host header
+    let info = router.get_handler_info("GET".into(), "HTTPS://api.example.com:443/users/7".into())
+        .unwrap()
+        .unwrap();
+    assert_eq!(info.id, api);
+    let info = router.get_handler_info_for_host("www.example.com".into(), "GET".into(), "http://api.example.com/users/7".into())
+        .unwrap()
+        .unwrap();
+    assert_eq!(info.id, api);
+
+    // Origin-form is unaffected
+    assert_eq!(router.get_handler_info("GET".into(), "/users/7".into()).unwrap().unwrap().id, users);
+    assert!(router.get_handler_info("GET".into(), "http:///users/7".into()).is_err());
+}
+
+#[test]
+fn test_dump_trie() {
+    let router = Router::new(Hooks::new());
+    let list = router.register("GET".into(), "/users".into(), None).unwrap();
+    let show = router.register("GET".into(), "/users/:id".into(), None).unwrap();
+    let files = router.register("GET".into(), "/files/*".into(), None).unwrap();
+    let status = router.host("api.example.com").register("GET", "/status", None).unwrap();
+
+    let expected = format!(
+        "GET\n  files\n    * [#{files}]\n  users [#{list}]\n    :id [#{show}]\n\
+         host api.example.com\n  GET\n    status [#{status}]\n"
+    );
+    assert_eq!(router.dump_trie(), expected);
+}
+
+#[test]
+fn test_empty_segments() {
+    let router = Router::new(Hooks::new());
+    let id = router.register("GET".into(), "/a/b".into(), None).unwrap();
+    assert_eq!(router.get_handler_info("GET".into(), "/a//b".into()).unwrap().unwrap().id, id);
+
+    let router = Router::new(Hooks::new());
+    router.set_allow_empty_segments(true);
+    let plain = router.register("GET".into(), "/a/b".into(), None).unwrap();
+    let doubled = router.register("GET".into(), "/a//b".into(), None).unwrap();
+    let keys = router.register("GET".into(), "/keys/:bucket/*".into(), None).unwrap();
+
+    assert_eq!(router.get_handler_info("GET".into(), "/a/b".into()).unwrap().unwrap().id, plain);
+    assert_eq!(router.get_handler_info("GET".into(), "/a//b".into()).unwrap().unwrap().id, doubled);
+    assert!(router.get_handler_info("GET".into(), "/a/b/".into()).unwrap().is_none());
+
+    let info = router.get_handler_info("GET".into(), "/keys/photos//2024/a.jpg".into()).unwrap().unwrap();
+    assert_eq!(info.id, keys);
+    assert_eq!(info.params.params.get("*").unwrap(), "/2024/a.jpg");
+    // Params never bind an empty segment
+    assert!(router.get_handler_info("GET".into(), "/keys//x".into()).unwrap().is_none());
+}
+
+#[test]
+fn test_route_meta() {
+    let router = Router::new(Hooks::new());
+    let admin = router.register("DELETE".into(), "/users/:id".into(), Some(RouteConfig {
+        meta: Some([("scope".to_string(), json!("admin"))].into_iter().collect()),
+        ..Default::default()
+    })).unwrap();
+    let public = router.register("GET".into(), "/users/:id".into(), None).unwrap();
+
+    assert_eq!(router.get_route_meta(admin).unwrap().get("scope").unwrap(), "admin");
+    assert!(router.get_route_meta(public).is_none());
+}
+
+#[test]
+fn test_explain_match() {
+    let router = Router::new(Hooks::new());
+    let latest = router.register("GET".into(), "/archive/latest".into(), None).unwrap();
+    router.register("GET".into(), "/archive/:year/:month".into(), None).unwrap();
+    router.register("POST".into(), "/archive".into(), None).unwrap();
+
+    let explanation = router.explain_match("GET".into(), "/archive/latest".into()).unwrap();
+    assert_eq!(explanation.handler_id, Some(latest));
+    assert!(explanation.reason.is_none());
+    let edges: Vec<_> = explanation.steps.iter().map(|step| step.edge.as_deref().unwrap()).collect();
+    assert_eq!(edges, ["GET", "archive", "latest"]);
+    assert_eq!(explanation.steps[2].alternatives, ["latest", ":year"]);
+
+    let explanation = router.explain_match("GET".into(), "/archive/2024".into()).unwrap();
+    assert_eq!(explanation.handler_id, None);
+    assert_eq!(explanation.reason.as_deref(), Some("path ends at a node without a handler"));
+    assert_eq!(explanation.steps.last().unwrap().edge.as_deref(), Some(":year"));
+
+    let explanation = router.explain_match("GET".into(), "/posts/1".into()).unwrap();
+    assert_eq!(explanation.handler_id, None);
+    let step = explanation.steps.last().unwrap();
+    assert_eq!(step.segment, "posts");
+    assert!(step.edge.is_none());
+    assert_eq!(step.alternatives, ["archive"]);
+    assert_eq!(explanation.reason.as_deref(), Some("no edge matches segment 'posts'"));
+}
+
+#[test]
+fn test_star_method_is_not_a_wildcard() {
+    let router = Router::new(Hooks::new());
+    let root = router.register("*".into(), "".into(), None).unwrap();
+    let x = router.register("*".into(), "/x".into(), None).unwrap();
+
+    assert!(router.get_handler_info("GET".into(), "/anything/here".into()).unwrap().is_none());
+    assert_eq!(router.get_handler_info("*".into(), "/x".into()).unwrap().unwrap().id, x);
+    assert_eq!(router.get_handler_info("*".into(), "/".into()).unwrap().unwrap().id, root);
+    assert!(router.allowed_methods("/anything/here".into()).unwrap().is_empty());
+}
@@ -0,0 +1,163 @@
+use serde::Deserialize;
+use serde_json::json;
+use crate::router::{RouteParams, TrieNode};
+
+#[test]
+fn test_most_specific_route_wins() {
+    let mut trie = TrieNode::new();
+    trie.insert("GET/a/:b/c/d", 1);
+    trie.insert("GET/a/b/:c/:d", 2);
+
+    // Three static segments beat two, even though `/a/b` is static first
+    let (id, params) = trie.find("GET/a/b/c/d").unwrap();
+    assert_eq!(id, 1);
+    assert_eq!(params.params.get("b").unwrap(), "b");
+}
+
+#[test]
+fn test_specificity_ties_prefer_earlier_static() {
+    let mut trie = TrieNode::new();
+    trie.insert("GET/a/:b/c", 1);
+    trie.insert("GET/a/b/:c", 2);
+    trie.insert("GET/a/*", 3);
+
+    let (id, params) = trie.find("GET/a/b/c").unwrap();
+    assert_eq!(id, 2);
+    assert_eq!(params.params.get("c").unwrap(), "c");
+    assert!(!params.params.contains_key("b"));
+
+    assert_eq!(trie.find("GET/a/x/c").unwrap().0, 1);
+    assert_eq!(trie.find("GET/a/x/y").unwrap().0, 3);
+}
+
+#[test]
+fn test_static_and_param_siblings_backtrack() {
+    let mut trie = TrieNode::new();
+    trie.insert("GET/archive/latest", 1);
+    trie.insert("GET/archive/:year/:month", 2);
+    trie.insert("GET/:year/:month/:day", 3);
+
+    // Static wins when it matches all the way down
+    let (id, params) = trie.find("GET/archive/latest").unwrap();
+    assert_eq!(id, 1);
+    assert!(params.params.is_empty());
+
+    // `latest` matches the static child, but only the param branch has a
+    // second segment, so lookup must fall back to it
+    let (id, params) = trie.find("GET/archive/latest/05").unwrap();
+    assert_eq!(id, 2);
+    assert_eq!(params.params.get("year").unwrap(), "latest");
+    assert_eq!(params.params.get("month").unwrap(), "05");
+
+    let (id, params) = trie.find("GET/archive/2024/05").unwrap();
+    assert_eq!(id, 2);
+    assert_eq!(params.params.get("year").unwrap(), "2024");
+
+    // Consecutive params
+    let (id, params) = trie.find("GET/2024/05/17").unwrap();
+    assert_eq!(id, 3);
+    assert_eq!(params.params.get("year").unwrap(), "2024");
+    assert_eq!(params.params.get("month").unwrap(), "05");
+    assert_eq!(params.params.get("day").unwrap(), "17");
+}
+
+#[test]
+fn test_wildcard_captures_raw_remainder() {
+    let mut trie = TrieNode::new();
+    trie.insert("GET/proxy/*", 1);
+
+    let (id, params) = trie.find("GET/proxy/a//b/c%2Fd").unwrap();
+    assert_eq!(id, 1);
+    assert_eq!(params.params.get("*").unwrap(), "a//b/c%2Fd");
+
+    let (_, params) = trie.find("GET/proxy/a/b/").unwrap();
+    assert_eq!(params.params.get("*").unwrap(), "a/b/");
+}
+
+#[test]
+fn test_backtracking_does_not_leak_params() {
+    let mut trie = TrieNode::new();
+    trie.insert("GET/:org/:repo/:branch/:path/raw", 1);
+    trie.insert("GET/:org/:repo/tree/:ref/:file", 2);
+    trie.insert("GET/:org/*", 3);
+
+    // Route 1 binds four params before failing on the last segment
+    let (id, params) = trie.find("GET/acme/zap/tree/main/lib.rs").unwrap();
+    assert_eq!(id, 2);
+    let mut keys: Vec<_> = params.params.keys().cloned().collect();
+    keys.sort();
+    assert_eq!(keys, ["file", "org", "ref", "repo"]);
+    assert_eq!(params.params.get("ref").unwrap(), "main");
+
+    let (id, params) = trie.find("GET/acme/a/b/c/d/e").unwrap();
+    assert_eq!(id, 3);
+    let mut keys: Vec<_> = params.params.keys().cloned().collect();
+    keys.sort();
+    assert_eq!(keys, ["*", "org"]);
+}
+
+#[test]
+fn test_params_to_json() {
+    let mut trie = TrieNode::new();
+    trie.insert("GET/users/:id/posts/:postId", 1);
+
+    let (_, mut params) = trie.find("GET/users/123/posts/456").unwrap();
+    params.parse_query(Some("page=2&sort=desc"));
+
+    assert_eq!(params.to_json(), json!({
+        "path": { "id": "123", "postId": "456" },
+        "query": { "page": "2", "sort": "desc" }
+    }));
+    assert_eq!(
+        params.to_json().to_string(),
+        r#"{"path":{"id":"123","postId":"456"},"query":{"page":"2","sort":"desc"}}"#
+    );
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Pagination {
+    page: u32,
+    limit: u32,
+    sort: Option<String>,
+    #[serde(default)]
+    tag: Vec<String>,
+}
+
+#[test]
+fn test_deserialize_query() {
+    let mut params = RouteParams::new();
+    params.parse_query(Some("page=2&limit=50"));
+    assert_eq!(params.deserialize_query::<Pagination>().unwrap(), Pagination {
+        page: 2,
+        limit: 50,
+        sort: None,
+        tag: Vec::new(),
+    });
+
+    params.parse_query(Some("tag=a&page=1&tag=b&limit=10&sort=desc"));
+    let pagination: Pagination = params.deserialize_query().unwrap();
+    assert_eq!(pagination.tag, ["a", "b"]);
+    assert_eq!(pagination.sort.as_deref(), Some("desc"));
+
+    params.parse_query(Some("page=2"));
+    let error = params.deserialize_query::<Pagination>().unwrap_err();
+    assert_eq!(error.status(), 400);
+    assert!(error.message.contains("limit"), "{}", error);
+
+    params.parse_query(Some("page=two&limit=5"));
+    let error = params.deserialize_query::<Pagination>().unwrap_err();
+    assert_eq!(error.status(), 400);
+    assert!(error.message.contains("page"), "{}", error);
+}
+
+#[test]
+fn test_query_is_percent_decoded() {
+    let mut params = RouteParams::new();
+    params.parse_query(Some("page=1&limit=5&sort=new%20first&tag=rust+lang&tag=a%26b&first%20name=Ada"));
+    assert_eq!(params.query.get("sort").unwrap(), "new first");
+    assert_eq!(params.query.get("first name").unwrap(), "Ada");
+
+    let pagination: Pagination = params.deserialize_query().unwrap();
+    assert_eq!(pagination.sort.as_deref(), Some("new first"));
+    assert_eq!(pagination.tag, ["rust lang", "a&b"]);
+}
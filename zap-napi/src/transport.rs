@@ -0,0 +1,463 @@
+//! In-process request handling on top of the native router. `JsRouter`
+//! runs routing, guards, middleware and handlers natively, so Rust
+//! embedders get the full request pipeline without a JS runtime; the
+//! integration tests drive the router through it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::error::{ErrorKind, ZapError};
+use crate::handle::Handle;
+use crate::hooks::Hooks;
+use crate::router::{LookupResult, RouteConfig, Router};
+use crate::types::{JsRequest, JsResponse};
+
+pub type BoxFuture = Pin<Box<dyn Future<Output = Result<JsResponse, ZapError>> + Send>>;
+pub type Next = Box<dyn FnOnce(JsRequest) -> BoxFuture + Send>;
+pub type Middleware = Box<dyn Fn(JsRequest, Next) -> BoxFuture + Send + Sync>;
+pub type DeprecationSink = Arc<dyn Fn(u32, &str) + Send + Sync>;
+pub type Finalizer = Box<dyn Fn(&mut JsResponse) + Send + Sync>;
+pub type GuardFn = Box<dyn Fn(&JsRequest) -> bool + Send + Sync>;
+pub type ErrorMapper = Box<dyn Fn(ZapError) -> ZapError + Send + Sync>;
+/// Minimum time between two warnings about hits on the same deprecated route.
+const DEPRECATION_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+/// Longest inbound request id that is trusted rather than replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+pub type ErrorHandler = Arc<dyn Fn(&ZapError, JsRequest) -> JsResponse + Send + Sync>;
+
+pub struct JsRouter {
+    router: Router,
+    handlers: Mutex<HashMap<u32, Handle>>,
+    /// Handlers of versioned routes by route id, then API version.
+    versions: Mutex<HashMap<u32, BTreeMap<u32, Handle>>>,
+    versioned_routes: Mutex<HashMap<(String, String), u32>>,
+    middlewares: Mutex<Vec<Arc<Middleware>>>,
+    guards: Mutex<HashMap<u32, Arc<GuardFn>>>,
+    next_guard_id: AtomicU32,
+    error_mappers: Mutex<Vec<ErrorMapper>>,
+    error_handlers: Mutex<HashMap<u32, ErrorHandler>>,
+    default_content_type: Mutex<Option<String>>,
+    server_header: Mutex<ServerHeader>,
+    finalizers: Mutex<Vec<Finalizer>>,
+    request_deadline: Mutex<Option<Duration>>,
+    request_id_header: Mutex<Option<String>>,
+    deprecation_warnings: Mutex<HashMap<u32, Instant>>,
+    deprecation_sink: Mutex<Option<DeprecationSink>>,
+}
+
+/// What `handle` does with the `Server` response header.
+enum ServerHeader {
+    /// Leave responses as the handler produced them.
+    Unset,
+    /// Add this value unless the handler set its own.
+    Fixed(String),
+    /// Remove any `Server` header.
+    Suppressed,
+}
+
+impl JsRouter {
+    pub fn new() -> Self {
+        Self {
+            router: Router::new(Hooks::new()),
+            handlers: Mutex::new(HashMap::new()),
+            versions: Mutex::new(HashMap::new()),
+            versioned_routes: Mutex::new(HashMap::new()),
+            middlewares: Mutex::new(Vec::new()),
+            guards: Mutex::new(HashMap::new()),
+            next_guard_id: AtomicU32::new(1),
+            error_mappers: Mutex::new(Vec::new()),
+            error_handlers: Mutex::new(HashMap::new()),
+            default_content_type: Mutex::new(None),
+            server_header: Mutex::new(ServerHeader::Unset),
+            finalizers: Mutex::new(Vec::new()),
+            request_deadline: Mutex::new(None),
+            request_id_header: Mutex::new(None),
+            deprecation_warnings: Mutex::new(HashMap::new()),
+            deprecation_sink: Mutex::new(None),
+        }
+    }
+
+    pub fn router(&self) -> &Router {
+        &self.router
+    }
+
+    /// Registers `handler` for `method` and `path`. `config` is stored on
+    /// the native router; its `timeoutMs` bounds the middleware chain and
+    /// handler, falling back to the router-wide timeout.
+    pub async fn add_route<F>(
+        &self,
+        method: String,
+        path: String,
+        config: Option<RouteConfig>,
+        handler: F,
+    ) -> Result<u32, ZapError>
+    where
+        F: Fn(JsRequest) -> Result<JsResponse, ZapError> + Send + Sync + 'static,
+    {
+        let id = self.router.register(method, path, config)?;
+        self.set_handler(id, handler);
+        Ok(id)
+    }
+
+    /// Registers `handler` as `version` of `method` and `path`. Versions of
+    /// one route share its id and `config`, which the first registration
+    /// sets. Requests pick a version with a vendor media type in `Accept`,
+    /// such as `application/vnd.myapi.v2+json`; without one they get the
+    /// latest version, and asking only for unregistered versions is a 406.
+    pub async fn add_versioned_route<F>(
+        &self,
+        method: String,
+        path: String,
+        version: u32,
+        config: Option<RouteConfig>,
+        handler: F,
+    ) -> Result<u32, ZapError>
+    where
+        F: Fn(JsRequest) -> Result<JsResponse, ZapError> + Send + Sync + 'static,
+    {
+        let key = (method.to_ascii_uppercase(), path.clone());
+        let mut versioned = self.versioned_routes.lock().unwrap();
+        let id = match versioned.get(&key) {
+            Some(id) => *id,
+            None => {
+                let id = self.router.register(method, path, config)?;
+                versioned.insert(key, id);
+                id
+            }
+        };
+        self.versions.lock().unwrap()
+            .entry(id)
+            .or_default()
+            .insert(version, Handle::new(handler));
+        Ok(id)
+    }
+
+    /// Attaches `handler` to a route registered directly on [`router`],
+    /// for example through a group or scope.
+    ///
+    /// [`router`]: JsRouter::router
+    pub fn set_handler<F>(&self, id: u32, handler: F)
+    where
+        F: Fn(JsRequest) -> Result<JsResponse, ZapError> + Send + Sync + 'static,
+    {
+        self.handlers.lock().unwrap().insert(id, Handle::new(handler));
+    }
+
+    /// Registers a guard and returns the id to list in a route's or group's
+    /// guards. Guards run before the middleware chain, in the route's
+    /// order; the first one to return `false` rejects the request with 403.
+    pub fn add_guard<F>(&self, guard: F) -> u32
+    where
+        F: Fn(&JsRequest) -> bool + Send + Sync + 'static,
+    {
+        let id = self.next_guard_id.fetch_add(1, Ordering::SeqCst);
+        self.guards.lock().unwrap().insert(id, Arc::new(Box::new(guard)));
+        id
+    }
+
+    /// Appends a middleware. Middlewares run in registration order around
+    /// the matched handler, each deciding whether to call `next`.
+    pub async fn add_middleware(&self, middleware: Middleware) -> Result<(), ZapError> {
+        self.middlewares.lock().unwrap().push(Arc::new(middleware));
+        Ok(())
+    }
+
+    /// Appends an error mapper. Errors returned by the middleware chain or
+    /// handler pass through every mapper, in registration order, before
+    /// `handle` returns them. Routing errors are not mapped.
+    pub fn map_err(&self, mapper: ErrorMapper) {
+        self.error_mappers.lock().unwrap().push(mapper);
+    }
+
+    /// Renders errors of `kind` as a response instead of returning them
+    /// from `handle`. Applies to routing errors and to errors from the
+    /// handler chain after mapping; a later handler for the same kind
+    /// replaces the earlier one.
+    pub fn on_error<F>(&self, kind: ErrorKind, handler: F) -> Result<u32, ZapError>
+    where
+        F: Fn(&ZapError, JsRequest) -> JsResponse + Send + Sync + 'static,
+    {
+        let id = self.router.on_error(kind, None)?;
+        self.error_handlers.lock().unwrap().insert(id, Arc::new(handler));
+        Ok(id)
+    }
+
+    /// Content type added to responses that have a non-empty body but no
+    /// `Content-Type`, e.g. `text/plain; charset=utf-8`. A content type set
+    /// by the handler or middleware is never replaced.
+    pub fn set_default_content_type(&self, mime: Option<String>) {
+        *self.default_content_type.lock().unwrap() = mime;
+    }
+
+    /// `Some(value)` adds `Server: value` to responses that don't set their
+    /// own; `None` strips the header from every response.
+    pub fn set_server_header(&self, value: Option<String>) {
+        *self.server_header.lock().unwrap() = match value {
+            Some(value) => ServerHeader::Fixed(value),
+            None => ServerHeader::Suppressed,
+        };
+    }
+
+    /// Appends a finalizer, the last step before a response leaves
+    /// `handle`. Finalizers run in registration order on every response,
+    /// including error responses: once one is registered, errors without
+    /// an error handler are rendered with [`JsResponse::from_error`]
+    /// instead of being returned.
+    pub fn add_finalizer(&self, finalizer: Finalizer) {
+        self.finalizers.lock().unwrap().push(finalizer);
+    }
+
+    /// Time budget for the whole of `handle`: routing, guards, middleware,
+    /// the handler and error handlers. A request that runs out gets a 504;
+    /// handlers can check [`JsRequest::remaining_time`]. `None` removes
+    /// the deadline.
+    pub fn set_request_deadline(&self, deadline: Option<Duration>) {
+        *self.request_deadline.lock().unwrap() = deadline;
+    }
+
+    /// Header carrying the request's correlation id, such as
+    /// `X-Request-Id`. An inbound id is kept when it is printable ASCII of
+    /// at most 128 characters, otherwise a UUID replaces it. The id is
+    /// available as [`JsRequest::request_id`] and echoed on the response
+    /// under the same name. `None` turns request ids off.
+    pub fn set_request_id_header(&self, name: Option<String>) {
+        *self.request_id_header.lock().unwrap() = name;
+    }
+
+    /// Reports hits on deprecated routes to `sink` with the route id and
+    /// `METHOD uri`, at most once per minute for each route. Hits go
+    /// unreported until a sink is set.
+    pub fn on_deprecated_hit<F>(&self, sink: F)
+    where
+        F: Fn(u32, &str) + Send + Sync + 'static,
+    {
+        *self.deprecation_sink.lock().unwrap() = Some(Arc::new(sink));
+    }
+
+    /// Undoes `set_server_header`, leaving the `Server` header to handlers.
+    pub fn reset_server_header(&self) {
+        *self.server_header.lock().unwrap() = ServerHeader::Unset;
+    }
+
+    pub async fn handle(&self, mut request: JsRequest) -> Result<JsResponse, ZapError> {
+        let request_id = self.assign_request_id(&mut request);
+        let deadline = *self.request_deadline.lock().unwrap();
+        let result = match deadline {
+            Some(budget) => {
+                let target = format!("{} {}", request.method, request.uri);
+                request.deadline = Some(Instant::now() + budget);
+                tokio::time::timeout(budget, self.respond(request)).await.unwrap_or_else(|_| {
+                    Err(ZapError::gateway_timeout(format!("{} exceeded the {}ms request deadline", target, budget.as_millis())))
+                })
+            }
+            None => self.respond(request).await,
+        };
+
+        let mut response = match result {
+            Ok(response) => response,
+            Err(error) if self.finalizers.lock().unwrap().is_empty() => return Err(error),
+            Err(error) => JsResponse::from_error(&error),
+        };
+        if let Some((name, id)) = request_id {
+            response.headers.retain(|key, _| !key.eq_ignore_ascii_case(&name));
+            response.headers.insert(name, vec![id]);
+        }
+        self.finalize(&mut response);
+        Ok(response)
+    }
+
+    /// Gives `request` its correlation id under the configured header,
+    /// keeping a valid inbound one. Returns the header name and id.
+    fn assign_request_id(&self, request: &mut JsRequest) -> Option<(String, String)> {
+        let name = self.request_id_header.lock().unwrap().clone()?;
+        let id = request.header(&name)
+            .filter(|id| is_valid_request_id(id))
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        request.headers.retain(|key, _| !key.eq_ignore_ascii_case(&name));
+        request.headers.insert(name.clone(), vec![id.clone()]);
+        request.request_id = Some(id.clone());
+        Some((name, id))
+    }
+
+    /// Dispatches `request`, rendering errors through a registered error
+    /// handler when there is one.
+    async fn respond(&self, request: JsRequest) -> Result<JsResponse, ZapError> {
+        let fallback = (!self.error_handlers.lock().unwrap().is_empty()).then(|| request.clone());
+        match self.dispatch(request).await {
+            Ok(response) => Ok(response),
+            Err(error) => match (self.error_handler(error.kind), fallback) {
+                (Some(handler), Some(request)) => Ok(handler(&error, request)),
+                _ => Err(error),
+            },
+        }
+    }
+
+    /// Applies the default content type, the `Server` header and the
+    /// finalizers.
+    fn finalize(&self, response: &mut JsResponse) {
+        if let Some(mime) = self.default_content_type.lock().unwrap().as_ref() {
+            let has_body = response.body.as_ref().is_some_and(|body| !body.is_empty());
+            if has_body && response.header("content-type").is_none() {
+                response.headers.insert("Content-Type".to_string(), vec![mime.clone()]);
+            }
+        }
+        match &*self.server_header.lock().unwrap() {
+            ServerHeader::Unset => {}
+            ServerHeader::Fixed(value) => {
+                if response.header("server").is_none() {
+                    response.headers.insert("Server".to_string(), vec![value.clone()]);
+                }
+            }
+            ServerHeader::Suppressed => {
+                response.headers.retain(|key, _| !key.eq_ignore_ascii_case("server"));
+            }
+        }
+        for finalizer in self.finalizers.lock().unwrap().iter() {
+            finalizer(response);
+        }
+    }
+
+    /// Routes `request`, by its `Host` header and then by path, and runs
+    /// the middleware chain and handler.
+    async fn dispatch(&self, mut request: JsRequest) -> Result<JsResponse, ZapError> {
+        let found = match request.header("host") {
+            Some(host) => self.router.lookup_for_host(host, &request.method, &request.uri)?,
+            None => self.router.lookup(&request.method, &request.uri)?,
+        };
+        let info = match found {
+            LookupResult::Found(info) => info,
+            LookupResult::MethodNotAllowed(allowed) => {
+                return Err(ZapError::method_not_allowed(format!("{} {}", request.method, request.uri), &allowed));
+            }
+            LookupResult::NotFound => {
+                return Err(ZapError::not_found(format!("{} {}", request.method, request.uri)));
+            }
+        };
+        let handler = self.select_handler(info.id, &request)?;
+
+        request.params.extend(info.params.params);
+        request.query.extend(info.params.query);
+
+        request.route_meta = self.router.get_route_meta(info.id);
+        let target = format!("{} {}", request.method, request.uri);
+        for guard_id in self.router.get_guard_ids(info.id) {
+            let guard = self.guards.lock().unwrap()
+                .get(&guard_id)
+                .cloned()
+                .ok_or_else(|| ZapError::internal(format!("unknown guard {} on {}", guard_id, target)))?;
+            if !guard(&request) {
+                return Err(ZapError::forbidden(target));
+            }
+        }
+        let deprecation = self.router.get_deprecation_headers(info.id);
+        if deprecation.is_some() {
+            self.warn_deprecated(info.id, &target);
+        }
+        let middlewares = Arc::new(self.middlewares.lock().unwrap().clone());
+        let chain = run(middlewares, 0, handler, request);
+        let result = match self.router.get_timeout(info.id) {
+            Some(timeout_ms) => tokio::time::timeout(Duration::from_millis(timeout_ms.into()), chain)
+                .await
+                .unwrap_or_else(|_| Err(ZapError::gateway_timeout(format!("{} timed out after {}ms", target, timeout_ms)))),
+            None => chain.await,
+        };
+        let mut response = result.map_err(|error| {
+            self.error_mappers.lock().unwrap()
+                .iter()
+                .fold(error, |error, mapper| mapper(error))
+        })?;
+
+        for (name, value) in deprecation.unwrap_or_default() {
+            if response.header(&name).is_none() {
+                response.headers.insert(name, vec![value]);
+            }
+        }
+        Ok(response)
+    }
+
+    /// Passes a hit on a deprecated route to the sink, at most once per
+    /// `DEPRECATION_WARNING_INTERVAL` for each route.
+    fn warn_deprecated(&self, id: u32, target: &str) {
+        let Some(sink) = self.deprecation_sink.lock().unwrap().clone() else {
+            return;
+        };
+        {
+            let mut warnings = self.deprecation_warnings.lock().unwrap();
+            let now = Instant::now();
+            if warnings.get(&id).is_some_and(|last| now.duration_since(*last) < DEPRECATION_WARNING_INTERVAL) {
+                return;
+            }
+            warnings.insert(id, now);
+        }
+        sink(id, target);
+    }
+
+    /// The handler for route `id`; for versioned routes, the version
+    /// requested in `Accept`.
+    fn select_handler(&self, id: u32, request: &JsRequest) -> Result<Handle, ZapError> {
+        let target = || format!("{} {}", request.method, request.uri);
+        if let Some(versions) = self.versions.lock().unwrap().get(&id) {
+            let requested = requested_versions(request);
+            let handler = if requested.is_empty() {
+                versions.values().next_back()
+            } else {
+                requested.iter().find_map(|version| versions.get(version))
+            };
+            let supported = versions.keys().map(|version| format!("v{}", version)).collect::<Vec<_>>();
+            return handler.cloned().ok_or_else(|| {
+                ZapError::not_acceptable(format!("{} supports API versions {}", target(), supported.join(", ")))
+            });
+        }
+        self.handlers.lock().unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| ZapError::not_found(target()))
+    }
+
+    fn error_handler(&self, kind: ErrorKind) -> Option<ErrorHandler> {
+        let id = self.router.get_error_handler(kind)?;
+        self.error_handlers.lock().unwrap().get(&id).cloned()
+    }
+}
+
+impl Default for JsRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether an inbound request id is safe to log and echo.
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+/// API versions named by vendor media types in `Accept`, such as
+/// `application/vnd.myapi.v2+json`, in the order listed.
+fn requested_versions(request: &JsRequest) -> Vec<u32> {
+    request
+        .headers_all("accept")
+        .iter()
+        .flat_map(|value| value.split(','))
+        .filter_map(|media_type| {
+            let essence = media_type.split(';').next()?.trim().to_ascii_lowercase();
+            let vendor = essence.strip_prefix("application/vnd.")?;
+            let name = vendor.split('+').next()?;
+            name.rsplit_once(".v")?.1.parse().ok()
+        })
+        .collect()
+}
+
+fn run(middlewares: Arc<Vec<Arc<Middleware>>>, index: usize, handler: Handle, request: JsRequest) -> BoxFuture {
+    match middlewares.get(index).cloned() {
+        Some(middleware) => {
+            let next: Next = Box::new(move |request| run(middlewares, index + 1, handler, request));
+            middleware(request, next)
+        }
+        None => Box::pin(async move { handler.handle(request) }),
+    }
+}
@@ -1,51 +1,66 @@
 use napi_derive::napi;
-use napi::{Result, Env, JsObject, JsString};
+use napi::{Either, Result, Env, JsObject, JsString};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use crate::error::ZapError;
 
 #[napi]
+#[derive(Debug, Clone)]
 pub struct JsRequest {
     pub method: String,
     pub uri: String,
+    /// Every value of each header, in the order received.
+    pub headers: HashMap<String, Vec<String>>,
     pub params: HashMap<String, String>,
     pub query: HashMap<String, String>,
     pub body: Option<String>,
+    /// `meta` of the matched route, attached by `JsRouter` before guards
+    /// and middleware run.
+    pub(crate) route_meta: Option<HashMap<String, Value>>,
+    /// When the request deadline set by `JsRouter` expires.
+    pub(crate) deadline: Option<Instant>,
+    /// Locale chosen by the `middleware::locale` middleware.
+    pub(crate) locale: Option<String>,
+    /// Correlation id assigned by `JsRouter`.
+    pub(crate) request_id: Option<String>,
 }
 
 impl JsRequest {
+    pub fn new(method: impl Into<String>, uri: impl Into<String>) -> Self {
+        Self {
+            method: method.into(),
+            uri: uri.into(),
+            headers: HashMap::new(),
+            params: HashMap::new(),
+            query: HashMap::new(),
+            body: None,
+            route_meta: None,
+            deadline: None,
+            locale: None,
+            request_id: None,
+        }
+    }
+
     pub fn from_object(obj: JsObject) -> Result<Self> {
         let method = obj.get_named_property::<String>("method")?;
         let uri = obj.get_named_property::<String>("uri")?;
-        
-        let params_obj: JsObject = obj.get_named_property("params")?;
-        let mut params = HashMap::new();
-        let param_keys = params_obj.get_property_names()?;
-        for i in 0..param_keys.get_array_length()? {
-            let key = param_keys.get_element::<JsString>(i)?;
-            let key_str = key.into_utf8()?.into_owned()?;
-            if let Ok(value) = params_obj.get_named_property::<String>(&key_str) {
-                params.insert(key_str, value);
-            }
-        }
-
-        let query_obj: JsObject = obj.get_named_property("query")?;
-        let mut query = HashMap::new();
-        let query_keys = query_obj.get_property_names()?;
-        for i in 0..query_keys.get_array_length()? {
-            let key = query_keys.get_element::<JsString>(i)?;
-            let key_str = key.into_utf8()?.into_owned()?;
-            if let Ok(value) = query_obj.get_named_property::<String>(&key_str) {
-                query.insert(key_str, value);
-            }
-        }
-
+        let headers = header_map(obj.get_named_property("headers")?)?;
+        let params = string_map(obj.get_named_property("params")?)?;
+        let query = string_map(obj.get_named_property("query")?)?;
         let body = obj.get_named_property::<Option<String>>("body")?;
 
         Ok(JsRequest {
             method,
             uri,
+            headers,
             params,
             query,
             body,
+            route_meta: None,
+            deadline: None,
+            locale: None,
+            request_id: None,
         })
     }
 
@@ -54,18 +69,9 @@ impl JsRequest {
 
         obj.set_named_property("method", &self.method)?;
         obj.set_named_property("uri", &self.uri)?;
-
-        let mut params_obj = env.create_object()?;
-        for (key, value) in &self.params {
-            params_obj.set_named_property(key, value)?;
-        }
-        obj.set_named_property("params", params_obj)?;
-
-        let mut query_obj = env.create_object()?;
-        for (key, value) in &self.query {
-            query_obj.set_named_property(key, value)?;
-        }
-        obj.set_named_property("query", query_obj)?;
+        obj.set_named_property("headers", header_object(env, &self.headers)?)?;
+        obj.set_named_property("params", string_object(env, &self.params)?)?;
+        obj.set_named_property("query", string_object(env, &self.query)?)?;
 
         if let Some(body) = &self.body {
             obj.set_named_property("body", body)?;
@@ -73,18 +79,88 @@ impl JsRequest {
 
         Ok(obj)
     }
+
+    /// First value of header `name`, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        first_header(&self.headers, name)
+    }
+
+    /// Every value of header `name`, matched case-insensitively.
+    pub fn headers_all(&self, name: &str) -> Vec<String> {
+        all_headers(&self.headers, name)
+    }
+
+    /// Metadata of the route this request matched, such as required auth
+    /// scopes. `None` before routing and for routes without `meta`.
+    pub fn route_meta(&self) -> Option<&HashMap<String, Value>> {
+        self.route_meta.as_ref()
+    }
+
+    /// Time left before the request deadline, or `None` without one.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Locale negotiated for this request, or `None` when no locale
+    /// middleware ran.
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    /// Correlation id of this request, or `None` unless `JsRouter` has a
+    /// request-id header configured.
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
 }
 
 #[napi]
+#[derive(Debug, Clone)]
 pub struct JsResponse {
     pub status: i32,
+    pub headers: HashMap<String, Vec<String>>,
     pub body: Option<String>,
 }
 
 impl JsResponse {
+    /// The response clients get for `error`: its status and JSON body.
+    pub fn from_error(error: &ZapError) -> Self {
+        let headers = HashMap::from([("Content-Type".to_string(), vec!["application/json".to_string()])]);
+        Self {
+            status: error.status().into(),
+            headers,
+            body: Some(error.to_json().to_string()),
+        }
+    }
+
+    /// A 200 response the browser saves as `filename` instead of rendering.
+    /// Non-ASCII names get an RFC 5987 `filename*` alongside an ASCII
+    /// fallback; control characters, quotes and backslashes are dropped so
+    /// the name cannot break out of the header.
+    pub fn attachment(body: String, filename: &str, content_type: &str) -> Self {
+        let filename: String = filename.chars()
+            .filter(|c| !c.is_control() && *c != '"' && *c != '\\')
+            .collect();
+        let fallback: String = filename.chars()
+            .map(|c| if c.is_ascii() { c } else { '_' })
+            .collect();
+
+        let mut disposition = format!("attachment; filename=\"{}\"", fallback);
+        if !filename.is_ascii() {
+            disposition.push_str(&format!("; filename*=UTF-8''{}", encode_rfc5987(&filename)));
+        }
+
+        let headers = HashMap::from([
+            ("Content-Type".to_string(), vec![content_type.to_string()]),
+            ("Content-Disposition".to_string(), vec![disposition]),
+        ]);
+        JsResponse { status: 200, headers, body: Some(body) }
+    }
+
     pub fn to_object(&self, env: Env) -> Result<JsObject> {
         let mut obj = env.create_object()?;
         obj.set_named_property("status", self.status)?;
+        obj.set_named_property("headers", header_object(env, &self.headers)?)?;
         if let Some(body) = &self.body {
             obj.set_named_property("body", body)?;
         }
@@ -93,30 +169,99 @@ impl JsResponse {
 
     pub fn from_object(obj: JsObject) -> Result<Self> {
         let status = obj.get_named_property::<i32>("status")?;
+        let headers = header_map(obj.get_named_property("headers")?)?;
         let body = obj.get_named_property::<Option<String>>("body")?;
-        Ok(JsResponse { status, body })
+        Ok(JsResponse { status, headers, body })
+    }
+
+    /// First value of header `name`, matched case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        first_header(&self.headers, name)
+    }
+
+    /// Every value of header `name`, matched case-insensitively.
+    pub fn headers_all(&self, name: &str) -> Vec<String> {
+        all_headers(&self.headers, name)
     }
 }
 
-#[napi]
-pub struct ZapError {
-    pub code: String,
-    pub details: Option<String>,
+/// Percent-encodes everything outside RFC 5987 `attr-char`.
+fn encode_rfc5987(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
 }
 
-impl ZapError {
-    pub fn to_object(&self, env: Env) -> Result<JsObject> {
-        let mut obj = env.create_object()?;
-        obj.set_named_property("code", &self.code)?;
-        if let Some(details) = &self.details {
-            obj.set_named_property("details", details)?;
+fn first_header<'a>(headers: &'a HashMap<String, Vec<String>>, name: &str) -> Option<&'a str> {
+    headers.iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+        .find_map(|(_, values)| values.first())
+        .map(String::as_str)
+}
+
+fn all_headers(headers: &HashMap<String, Vec<String>>, name: &str) -> Vec<String> {
+    headers.iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+        .flat_map(|(_, values)| values.iter().cloned())
+        .collect()
+}
+
+/// Reads a headers object as Node provides it: a string per header, or an
+/// array of strings for repeated headers such as `set-cookie`.
+fn header_map(obj: JsObject) -> Result<HashMap<String, Vec<String>>> {
+    let mut map = HashMap::new();
+    let keys = obj.get_property_names()?;
+    for i in 0..keys.get_array_length()? {
+        let key = keys.get_element::<JsString>(i)?;
+        let key_str = key.into_utf8()?.into_owned()?;
+        match obj.get_named_property::<Either<String, Vec<String>>>(&key_str) {
+            Ok(Either::A(value)) => {
+                map.insert(key_str, vec![value]);
+            }
+            Ok(Either::B(values)) => {
+                map.insert(key_str, values);
+            }
+            Err(_) => {}
         }
-        Ok(obj)
     }
+    Ok(map)
+}
 
-    pub fn from_object(obj: JsObject) -> Result<Self> {
-        let code = obj.get_named_property::<String>("code")?;
-        let details = obj.get_named_property::<Option<String>>("details")?;
-        Ok(ZapError { code, details })
+/// Writes single-valued headers as strings and repeated ones as arrays.
+fn header_object(env: Env, headers: &HashMap<String, Vec<String>>) -> Result<JsObject> {
+    let mut obj = env.create_object()?;
+    for (key, values) in headers {
+        match values.as_slice() {
+            [value] => obj.set_named_property(key, value)?,
+            values => obj.set_named_property(key, values.to_vec())?,
+        }
     }
-} 
\ No newline at end of file
+    Ok(obj)
+}
+
+fn string_map(obj: JsObject) -> Result<HashMap<String, String>> {
+    let mut map = HashMap::new();
+    let keys = obj.get_property_names()?;
+    for i in 0..keys.get_array_length()? {
+        let key = keys.get_element::<JsString>(i)?;
+        let key_str = key.into_utf8()?.into_owned()?;
+        if let Ok(value) = obj.get_named_property::<String>(&key_str) {
+            map.insert(key_str, value);
+        }
+    }
+    Ok(map)
+}
+
+fn string_object(env: Env, map: &HashMap<String, String>) -> Result<JsObject> {
+    let mut obj = env.create_object()?;
+    for (key, value) in map {
+        obj.set_named_property(key, value)?;
+    }
+    Ok(obj)
+}
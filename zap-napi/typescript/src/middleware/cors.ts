@@ -3,6 +3,9 @@ import { Middleware } from '../types';
 export interface CorsOptions {
   origin?: string | string[] | boolean;
   methods?: string[];
+  /** Methods registered for a path, e.g. `path => router.allowedMethods(path)`.
+   * Preflight responses advertise these, falling back to `methods` when empty. */
+  allowedMethods?: (path: string) => string[];
   allowedHeaders?: string[];
   exposedHeaders?: string[];
   credentials?: boolean;
@@ -22,6 +25,21 @@ const defaultOptions: CorsOptions = {
   optionsSuccessStatus: 204
 };
 
+function preflightMethods(uri: string, options: CorsOptions): string[] {
+  if (options.allowedMethods) {
+    const path = uri.split('?')[0];
+    try {
+      const methods = options.allowedMethods(path);
+      if (methods.length > 0) {
+        return methods;
+      }
+    } catch {
+      // Paths the router rejects fall back to the configured list
+    }
+  }
+  return options.methods!;
+}
+
 function isOriginAllowed(origin: string, allowedOrigin: string | string[] | boolean): boolean {
   if (allowedOrigin === '*' || allowedOrigin === true) {
     return true;
@@ -36,13 +54,14 @@ export function cors(options: CorsOptions = {}): Middleware {
   const corsOptions = { ...defaultOptions, ...options };
 
   return async (request, next) => {
-    const origin = request.headers['origin'];
+    const originHeader = request.headers['origin'];
+    const origin = Array.isArray(originHeader) ? originHeader[0] : originHeader;
     const method = request.method;
 
     // Handle preflight requests
     if (method === 'OPTIONS') {
       const headers: Record<string, string> = {
-        'Access-Control-Allow-Methods': corsOptions.methods!.join(', '),
+        'Access-Control-Allow-Methods': preflightMethods(request.uri, corsOptions).join(', '),
         'Access-Control-Allow-Headers': corsOptions.allowedHeaders!.join(', '),
         'Access-Control-Max-Age': corsOptions.maxAge!.toString()
       };
@@ -73,6 +73,10 @@ export class Router {
     }
   }
 
+  allowedMethods(path: string): string[] {
+    return this.nativeRouter.allowedMethods(path);
+  }
+
   async registerMiddleware(middleware: Middleware): Promise<number> {
     const id = this.nextMiddlewareId++;
     this.middlewares.set(id, middleware);
@@ -22,7 +22,7 @@ export interface RouterOptions {
 export interface JsRequest {
   method: string;
   uri: string;
-  headers: Record<string, string>;
+  headers: Record<string, string | string[]>;
   body: any;
   params: Record<string, string>;
   query?: Record<string, string>;
@@ -41,7 +41,7 @@ export type ResponseBody = {
 
 export interface JsResponse {
   status: number;
-  headers: Record<string, string>;
+  headers: Record<string, string | string[]>;
   body: any;
   redirect?: string;
 }
@@ -315,4 +315,7 @@ export interface RouteConfig {
   middleware?: number[];
   validation?: number;
   transform?: number;
+  timeoutMs?: number;
+  deprecation?: { sunset?: string };
+  meta?: Record<string, unknown>;
 } 
\ No newline at end of file
@@ -6,6 +6,7 @@ declare module 'zap-napi' {
         constructor();
         register(method: string, path: string): HandlerId;
         getHandlerId(method: string, path: string): HandlerId | null;
+        allowedMethods(path: string): string[];
     }
 
     export class Hooks {
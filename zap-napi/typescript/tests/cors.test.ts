@@ -0,0 +1,32 @@
+import { cors } from '../src/middleware/cors';
+import { Router } from '../src/router';
+import { JsRequest, JsResponse } from '../src/types';
+
+describe('CORS', () => {
+  const preflight = (uri: string): JsRequest => ({
+    method: 'OPTIONS',
+    uri,
+    headers: { origin: 'https://example.com' },
+    body: null,
+    params: {}
+  });
+
+  it('advertises the methods registered for the path', async () => {
+    const router = new Router();
+    await router.get('/api/items', async () => ({ status: 200, headers: {}, body: null }));
+    await router.post('/api/items', async () => ({ status: 201, headers: {}, body: null }));
+
+    const middleware = cors({ allowedMethods: path => router.allowedMethods(path) });
+    const response = await middleware(preflight('/api/items?page=1'), async () => {}) as JsResponse;
+
+    expect(response.status).toBe(204);
+    expect(response.headers['Access-Control-Allow-Methods']).toBe('GET, POST');
+  });
+
+  it('falls back to the configured methods for unknown paths', async () => {
+    const middleware = cors({ methods: ['GET'], allowedMethods: () => [] });
+    const response = await middleware(preflight('/missing'), async () => {}) as JsResponse;
+
+    expect(response.headers['Access-Control-Allow-Methods']).toBe('GET');
+  });
+});